@@ -5,7 +5,12 @@ use serde::{Deserialize, Serialize};
 use std::env;
 use std::sync::{mpsc::channel, Arc, Mutex};
 use std::time::Duration;
-use tangleproof::{chronist::Chronist, error::Result, server, server::MessageIdResponse};
+use tangleproof::{
+    chronist::{Chronist, ChronistConfig},
+    error::Result,
+    server,
+    server::MessageIdResponse,
+};
 use tokio::time::sleep;
 extern crate dotenv;
 use dotenv::dotenv;
@@ -17,10 +22,13 @@ use reqwest;
 async fn main() -> Result<()> {
     dotenv().ok();
 
+    // a comma separated list of nodes fails over to the next node on errors
+    let node_urls = env::var("IOTA_NODE").unwrap();
     let chronist = Chronist::new(
         &"maindb",
-        &env::var("IOTA_NODE").unwrap(),
+        &node_urls.split(',').map(str::trim).collect::<Vec<&str>>(),
         &"256a818b2aac458941f2274945a410e57fb750f3a3a67969ece5bd9ae7eef5b2",
+        ChronistConfig::default(),
     )
     .await?;
     tokio::spawn(async move {
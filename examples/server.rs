@@ -2,7 +2,11 @@
 
 use iota_client::Client;
 use std::env;
-use tangleproof::{chronist::Chronist, error::Result, server};
+use tangleproof::{
+    chronist::{Chronist, ChronistConfig},
+    error::Result,
+    server,
+};
 extern crate dotenv;
 use dotenv::dotenv;
 
@@ -12,10 +16,15 @@ use dotenv::dotenv;
 async fn main() -> Result<()> {
     dotenv().ok();
 
+    let config = ChronistConfig::from_env()?;
+
+    // a comma separated list of nodes fails over to the next node on errors
+    let node_urls = env::var("IOTA_NODE").unwrap();
     let chronist = Chronist::new(
         &env::var("DB_PATH").unwrap(),
-        &env::var("IOTA_NODE").unwrap(),
+        &node_urls.split(',').map(str::trim).collect::<Vec<&str>>(),
         &Client::mnemonic_to_hex_seed(&env::var("MNEMONIC").unwrap())?,
+        config,
     )
     .await?;
 
@@ -1,6 +1,13 @@
 use iota_client::Client;
-use std::env;
-use tangleproof::{chronist::Chronist, error::Result, server};
+use std::{env, sync::Arc, time::Duration};
+use tangleproof::{
+    chronist::{Chronist, ChronistConfig},
+    error::Result,
+    node_pool::NodePool,
+    sender::BatchingPolicy,
+    server,
+    storage::StorageConfig,
+};
 extern crate dotenv;
 use dotenv::dotenv;
 
@@ -8,12 +15,40 @@ use dotenv::dotenv;
 async fn main() -> Result<()> {
     dotenv().ok();
 
-    let chronist = Chronist::new(
+    let config = ChronistConfig::from_env()?;
+
+    let storage = StorageConfig::from_name(
+        &env::var("STORAGE_BACKEND").unwrap_or_else(|_| "rocksdb".to_string()),
         &env::var("DB_PATH").unwrap(),
-        &env::var("IOTA_NODE").unwrap(),
+    )?
+    .open()?;
+    // a comma separated list of nodes fails over to the next node on errors
+    let node_urls = env::var("IOTA_NODE").unwrap();
+    let mut node_pool =
+        NodePool::from_urls(&node_urls.split(',').map(str::trim).collect::<Vec<&str>>()).await?;
+    if let Ok(quorum) = env::var("OUTPUT_QUORUM") {
+        node_pool = node_pool.with_quorum(quorum.parse()?)?;
+    }
+    let chronist = Chronist::with_storage(
+        storage,
+        Arc::new(node_pool),
         &Client::mnemonic_to_hex_seed(&env::var("MNEMONIC").unwrap())?,
+        config,
     )
     .await?;
+    // the setting is stored in the db, so it only changes if the variable is set
+    if let Ok(merkle_tree) = env::var("MERKLE_TREE") {
+        chronist.use_merkle_tree(merkle_tree == "true").await?;
+    }
+    let default_policy = BatchingPolicy::default();
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: env::var("BATCH_SIZE").map_or(Ok(default_policy.batch_size), |b| b.parse())?,
+        max_latency: env::var("BATCH_MAX_LATENCY_MS")
+            .map_or(Ok(default_policy.max_latency), |l| {
+                l.parse().map(Duration::from_millis)
+            })?,
+        ..default_policy
+    });
 
     server::start(chronist, 3030).await?;
     Ok(())
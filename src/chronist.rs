@@ -1,38 +1,248 @@
-use crate::{error::Result, iota_api::send_transaction, storage::RocksdbStorage};
+use crate::{
+    error::Result,
+    events::{ChronistEvent, EVENT_CHANNEL_CAPACITY},
+    iota_api::build_transaction,
+    ledger::{find_output_id, output_address_and_amount, LedgerBackend},
+    merkle_tree::{
+        content_hash_leaf, message_id_leaf, MerkleHash, MerkleProof, MerkleRoot, MerkleTree,
+    },
+    node_pool::NodePool,
+    pending_queue::{PendingEntry, PendingItem, PendingQueue, Priority},
+    sender::{BatchingPolicy, SenderGuard, SenderHandle},
+    storage::{
+        decode_message_id, Storage, StorageBatch, StorageColumn, StorageExt, StorageKey, PAGE_SIZE,
+    },
+    webhook::{self, WebhookDelivery},
+};
 use chrono::{DateTime, Utc};
 use iota_client::bee_message::prelude::{
-    Address, Essence, Message, MessageId, Output, OutputId, Payload,
+    Address, Essence, Input, Message, MessageId, OutputId, Payload, TransactionPayload,
+};
+use iota_client::{
+    crypto::hashes::{blake2b::Blake2b256, Digest},
+    Client,
 };
-use iota_client::{Client, Seed};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, str::FromStr, sync::Arc};
-use tokio::{
-    sync::{Mutex, RwLock},
-    time::sleep,
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
+use tokio::sync::{broadcast, Mutex, RwLock};
 
-const MESSAGE_IDS_KEY: &str = "message_ids";
-const TRANSACTION_INDEX_KEY: &str = "transaction_index";
-pub(crate) const TRANSACTION_MESSAGE_KEY: &str = "transaction_message";
 const CHRONIST_INDEX: &str = "Chronist";
-pub(crate) const INCLUSION_INDEX: &str = "inclusion_index";
+/// Version of the InclusionData in the indexation payloads, data without a version field is version 0.
+/// 0: only message ids
+/// 1: message ids, content hashes and an optional Merkle root instead of them
+pub const INCLUSION_DATA_VERSION: u8 = 1;
+// Message ids and hashes in a transaction with a Merkle root, the proof only has the audit path of one of them
+const MAX_MERKLE_TREE_LEAVES: usize = 10_000;
+// Hashes are about twice as long as message ids in the inclusion data
+const HASH_ENTRY_SIZE: usize = 2;
+/// A transaction can have at most 127 outputs, the split transaction needs one for each row and one for the remainder
+pub const MAX_ROWS: u64 = 126;
+
+/// Geometry of the inclusion structure and the amount that is locked in each output.
+/// It's stored in the db at the first start and can't be changed afterwards, because the positions of all
+/// existing transactions depend on it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChronistConfig {
+    /// Amount of rows, each row requires one output
+    pub rows: u64,
+    /// Transactions from the same utxo until one of the next row will be used
+    pub section_length: u64,
+    /// Amount of iotas in each output
+    pub amount: u64,
+}
+
+impl Default for ChronistConfig {
+    fn default() -> Self {
+        Self {
+            rows: 10,
+            section_length: 3,
+            amount: 1_000_000,
+        }
+    }
+}
 
-pub(crate) const INCLUSION_STRUCTURE_ROWS: u64 = 10;
-pub(crate) const INCLUSION_STRUCTURE_SECTION_LENGTH: u64 = 3;
+impl ChronistConfig {
+    /// Create a config, fails if it can't be used for an inclusion structure
+    pub fn new(rows: u64, section_length: u64, amount: u64) -> Result<Self> {
+        let config = Self {
+            rows,
+            section_length,
+            amount,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read the config from the optional `INCLUSION_STRUCTURE_ROWS`, `INCLUSION_STRUCTURE_SECTION_LENGTH` and
+    /// `OUTPUT_AMOUNT` env variables, unset ones keep their default value
+    pub fn from_env() -> Result<Self> {
+        let default_config = Self::default();
+        Self::new(
+            std::env::var("INCLUSION_STRUCTURE_ROWS")
+                .map_or(Ok(default_config.rows), |r| r.parse())?,
+            std::env::var("INCLUSION_STRUCTURE_SECTION_LENGTH")
+                .map_or(Ok(default_config.section_length), |s| s.parse())?,
+            std::env::var("OUTPUT_AMOUNT").map_or(Ok(default_config.amount), |a| a.parse())?,
+        )
+    }
+
+    /// Check that all values are > 0, that the split transaction with one output per row and the remainder fits
+    /// into a single transaction and that the positions of the inclusion structure and the required funds fit into a
+    /// u64
+    pub fn validate(&self) -> Result<()> {
+        if self.rows == 0 || self.section_length == 0 || self.amount == 0 {
+            return Err(crate::error::Error::InvalidParameter(
+                "rows, section_length and amount need to be > 0".to_string(),
+            ));
+        }
+        if self.rows > MAX_ROWS {
+            return Err(crate::error::Error::InvalidParameter(format!(
+                "rows need to be <= {}",
+                MAX_ROWS
+            )));
+        }
+        if self.inclusion_structure_end().is_none() {
+            return Err(crate::error::Error::InvalidParameter(
+                "rows and section_length are too large, the positions of the inclusion structure would overflow"
+                    .to_string(),
+            ));
+        }
+        if self.rows.checked_mul(self.amount).is_none() {
+            return Err(crate::error::Error::InvalidParameter(
+                "rows * amount would overflow".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Total amount of iotas that is required for the inclusion structure
+    pub fn required_funds(&self) -> u64 {
+        self.rows * self.amount
+    }
+
+    // Position after the end of the highest row, the largest value the position math computes. None if it overflows
+    fn inclusion_structure_end(&self) -> Option<u64> {
+        let mut end: u64 = 0;
+        for row in 0..=self.rows {
+            let row_length = self
+                .section_length
+                .checked_add(1)?
+                .checked_mul(self.section_length.checked_pow(row as u32)?)?;
+            end = end.checked_add(row_length)?;
+        }
+        Some(end)
+    }
+}
 
 pub struct Chronist {
-    pub(crate) db: Arc<Mutex<RocksdbStorage>>,
+    pub(crate) db: Arc<Mutex<Box<dyn Storage>>>,
+    pub ledger: Arc<dyn LedgerBackend>,
+    /// Client of the nodes for a Chronist created with `Chronist::new`, an offline client for other ledger backends
+    #[deprecated(
+        note = "use the ledger field, it also works with a node pool or a simulated ledger"
+    )]
     pub iota_client: Arc<Client>,
-    pub(crate) message_ids: Arc<RwLock<HashSet<MessageId>>>,
-    pub(crate) pending_message_ids: Arc<RwLock<HashSet<MessageId>>>,
+    /// Message ids and hashes that wait to be included
+    pub(crate) pending: Arc<RwLock<PendingQueue>>,
+    pub(crate) config: ChronistConfig,
     seed: String,
     sending_transacion: Arc<Mutex<()>>,
+    merkle_tree: Arc<AtomicBool>,
+    allow_private_callback_hosts: Arc<AtomicBool>,
+    events: broadcast::Sender<ChronistEvent>,
+    sender: SenderHandle,
+    // stops the sender when the Chronist is dropped, not set for the handles of the background tasks
+    _sender_guard: Option<SenderGuard>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InclusionData {
+    #[serde(default)]
+    pub version: u8,
     pub time: DateTime<Utc>,
     pub message_ids: Vec<MessageId>,
+    /// Hashes of documents, since version 1
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hashes: Vec<ContentHash>,
+    /// Root of a Merkle tree over the message ids and hashes, since version 1. If it's set, the message ids and
+    /// hashes aren't in the inclusion data and a proof needs the audit path to the root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merkle_root: Option<MerkleRoot>,
+}
+
+/// Algorithm that was used to hash a document
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Sha256,
+    Blake2b256,
+}
+
+impl HashAlgorithm {
+    /// Byte that identifies the algorithm in storage keys
+    pub fn tag(self) -> u8 {
+        match self {
+            HashAlgorithm::Sha256 => 0,
+            HashAlgorithm::Blake2b256 => 1,
+        }
+    }
+}
+
+/// Hash of a document that gets timestamped instead of a message
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentHash {
+    pub algorithm: HashAlgorithm,
+    /// Hex encoded digest
+    #[serde(with = "hex_digest")]
+    pub digest: [u8; 32],
+}
+
+impl ContentHash {
+    /// Hash raw data with BLAKE2b-256
+    pub fn from_data(data: &[u8]) -> Self {
+        Self {
+            algorithm: HashAlgorithm::Blake2b256,
+            digest: Blake2b256::digest(data).into(),
+        }
+    }
+
+    /// Use an existing hex encoded digest
+    pub fn from_hex(algorithm: HashAlgorithm, digest: &str) -> Result<Self> {
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(digest, &mut bytes)?;
+        Ok(Self {
+            algorithm,
+            digest: bytes,
+        })
+    }
+
+    /// Hex encoded digest
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.digest)
+    }
+}
+
+pub(crate) mod hex_digest {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(digest: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(digest))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        let digest = String::deserialize(deserializer)?;
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(digest, &mut bytes).map_err(D::Error::custom)?;
+        Ok(bytes)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -41,6 +251,91 @@ pub struct MessageWrapper {
     pub message: Message,
 }
 
+/// Page of included message ids, see `Chronist::get_message_ids`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageIdsPage {
+    pub message_ids: Vec<MessageId>,
+    /// Cursor for the next page, None if there are no more message ids
+    pub next_cursor: Option<MessageId>,
+}
+
+/// What `Chronist::rebuild_from_ledger` restored
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RebuildReport {
+    /// Amount of restored transactions
+    pub transactions: u64,
+    /// Included message ids whose message isn't available anymore
+    pub missing_messages: Vec<MessageId>,
+    /// Positions of transactions with a Merkle root, their message ids and hashes can't be restored
+    pub merkle_root_positions: Vec<u64>,
+}
+
+/// Place of a pending message id in the queue, see `Chronist::get_queue_position`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuePosition {
+    pub message_id: MessageId,
+    pub priority: Priority,
+    /// Amount of message ids and hashes that are sent before this one
+    pub position: usize,
+    /// Amount of pending message ids and hashes
+    pub pending: usize,
+}
+
+/// Result of storing a message for a proof
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum SaveMessageStatus {
+    /// Message got stored and will be included in the next transactions
+    Accepted,
+    /// Message is already included in the transaction at this position
+    AlreadyIncluded {
+        #[serde(rename = "inclusionPosition")]
+        inclusion_position: u64,
+    },
+    /// Message is already stored and waits to be included
+    AlreadyPending,
+    /// The node doesn't know the message
+    NotFoundOnNode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HashWrapper {
+    pub inclusion_position: Option<u64>,
+    pub hash: ContentHash,
+}
+
+// Data that is shared between proofs, so it only needs to be loaded once for multiple proofs
+struct ProofContext {
+    latest_position: u64,
+    addresses: Vec<Address>,
+    // position -> message id and transaction of the position
+    path_transactions: HashMap<u64, (MessageId, TransactionPayload)>,
+}
+
+// Transactions of a proof from the inclusion position to the latest position
+struct ProofChain {
+    latest_output_id: OutputId,
+    transactions: Vec<TransactionPayload>,
+    milestone_anchor: Option<crate::inclusion_proof::MilestoneAnchor>,
+}
+
+// Transaction that is about to be sent, stored before sending it so it can be recovered after a crash
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PendingTransaction {
+    position: u64,
+    message_ids: Vec<MessageId>,
+    hashes: Vec<ContentHash>,
+    merkle_tree: Option<MerkleTree>,
+    /// The built transaction, it's posted after it got stored
+    message: Message,
+}
+
+// Message ids and hashes of the sent transactions that aren't confirmed yet, by position
+type SentEntries = HashMap<u64, (Vec<MessageId>, Vec<ContentHash>)>;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UtxoData {
     pub position_index: u64,
@@ -48,388 +343,1578 @@ pub struct UtxoData {
 }
 
 impl Chronist {
-    pub async fn new(path: &str, node_url: &str, seed: &str) -> Result<Self> {
-        let db = Arc::new(Mutex::new(RocksdbStorage::new(path)?));
-        let iota_client = Arc::new(Client::builder().with_node(node_url)?.finish().await?);
+    /// Create a Chronist with a RocksDB at the path that uses the nodes, requests fail over to the next node on errors
+    #[cfg(feature = "rocksdb")]
+    pub async fn new(
+        path: &str,
+        node_urls: &[&str],
+        seed: &str,
+        config: ChronistConfig,
+    ) -> Result<Self> {
+        let ledger = Arc::new(NodePool::from_urls(node_urls).await?);
+        let iota_client = Arc::new(Client::builder().with_nodes(node_urls)?.finish().await?);
+        let storage = Box::new(crate::storage::RocksdbStorage::new(path)?);
+        Self::with_storage_and_client(storage, ledger, iota_client, seed, config).await
+    }
+
+    /// Create a Chronist that uses the provided ledger backend instead of a node
+    #[cfg(feature = "rocksdb")]
+    pub async fn with_ledger(
+        path: &str,
+        ledger: Arc<dyn LedgerBackend>,
+        seed: &str,
+        config: ChronistConfig,
+    ) -> Result<Self> {
+        let storage = Box::new(crate::storage::RocksdbStorage::new(path)?);
+        Self::with_storage(storage, ledger, seed, config).await
+    }
+
+    /// Create a Chronist with another storage backend than RocksDB, see `StorageConfig::open`
+    pub async fn with_storage(
+        storage: Box<dyn Storage>,
+        ledger: Arc<dyn LedgerBackend>,
+        seed: &str,
+        config: ChronistConfig,
+    ) -> Result<Self> {
+        let iota_client = Arc::new(
+            Client::builder()
+                .with_offline_mode()
+                .with_node_sync_disabled()
+                .finish()
+                .await?,
+        );
+        Self::with_storage_and_client(storage, ledger, iota_client, seed, config).await
+    }
+
+    #[allow(deprecated)]
+    async fn with_storage_and_client(
+        storage: Box<dyn Storage>,
+        ledger: Arc<dyn LedgerBackend>,
+        iota_client: Arc<Client>,
+        seed: &str,
+        config: ChronistConfig,
+    ) -> Result<Self> {
+        config.validate()?;
+        let db = Arc::new(Mutex::new(storage));
 
         // init transaction index
         let db_ = db.clone();
         let mut database = db_.lock().await;
-        if database.get(TRANSACTION_INDEX_KEY).await.is_err() {
-            crate::iota_api::split_funds(&iota_client, INCLUSION_STRUCTURE_ROWS, seed).await?;
-            database.set(TRANSACTION_INDEX_KEY, "1".to_string()).await?;
-            database.set(MESSAGE_IDS_KEY, "[]".to_string()).await?;
+        if database
+            .get::<u64>(&StorageKey::TransactionIndex)
+            .await
+            .is_err()
+        {
+            crate::iota_api::split_funds(ledger.as_ref(), &config, seed).await?;
+            database.set(&StorageKey::ChronistConfig, &config).await?;
+            database.set(&StorageKey::TransactionIndex, &1u64).await?;
         }
 
-        let message_ids: HashSet<MessageId> =
-            serde_json::from_str(&database.get(MESSAGE_IDS_KEY).await?)?;
+        // dbs created before the config was stored used the default geometry
+        let stored_config: ChronistConfig = match database.get(&StorageKey::ChronistConfig).await {
+            Ok(stored_config) => stored_config,
+            Err(crate::error::Error::RecordNotFound) => {
+                let default_config = ChronistConfig::default();
+                database
+                    .set(&StorageKey::ChronistConfig, &default_config)
+                    .await?;
+                default_config
+            }
+            Err(e) => return Err(e),
+        };
+        if stored_config != config {
+            return Err(crate::error::Error::ConfigMismatch(stored_config, config));
+        }
+
+        migrate_message_ids(&mut database).await?;
+        // dbs created before the confirmed position was stored confirmed each transaction before sending the next one
+        let confirmed_transaction_index =
+            match database.get(&StorageKey::ConfirmedTransactionIndex).await {
+                Ok(confirmed_transaction_index) => confirmed_transaction_index,
+                Err(crate::error::Error::RecordNotFound) => {
+                    database.get::<u64>(&StorageKey::TransactionIndex).await? - 1
+                }
+                Err(e) => return Err(e),
+            };
+        // callbacks of confirmed messages that didn't get enqueued before a crash
+        enqueue_webhooks(&mut database, confirmed_transaction_index).await?;
+        let merkle_tree = match database.get(&StorageKey::MerkleTreeEnabled).await {
+            Ok(merkle_tree) => merkle_tree,
+            Err(crate::error::Error::RecordNotFound) => false,
+            Err(e) => return Err(e),
+        };
+        drop(database);
 
+        let sender = SenderHandle::new();
         let chronist = Self {
             db,
+            ledger,
             iota_client,
-            message_ids: Arc::new(RwLock::new(message_ids)),
-            pending_message_ids: Arc::new(RwLock::new(HashSet::new())),
+            pending: Arc::new(RwLock::new(PendingQueue::default())),
+            config,
             seed: seed.to_owned(),
             sending_transacion: Arc::new(Mutex::new(())),
+            merkle_tree: Arc::new(AtomicBool::new(merkle_tree)),
+            allow_private_callback_hosts: Arc::new(AtomicBool::new(false)),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            sender: sender.clone(),
+            _sender_guard: Some(SenderGuard::new(sender)),
         };
-        let chronist_ = Self {
-            db: chronist.db.clone(),
-            iota_client: chronist.iota_client.clone(),
-            message_ids: chronist.message_ids.clone(),
-            pending_message_ids: chronist.pending_message_ids.clone(),
-            seed: seed.to_owned(),
-            sending_transacion: chronist.sending_transacion.clone(),
-        };
-        chronist_.start_sending_transactions();
+        // the pending messages can only be loaded after the recovery, because it could include some of them
+        let recovered = chronist.recover_pending_transaction().await?;
+        let pending = load_pending(&**chronist.db.lock().await).await?;
+        *chronist.pending.write().await = pending;
+
+        chronist.handle().start_sending_transactions(recovered);
         Ok(chronist)
     }
 
-    fn start_sending_transactions(self) {
-        let pending_message_ids = self.pending_message_ids.clone();
-        std::thread::spawn(move || {
-            let runtime = tokio::runtime::Builder::new_multi_thread()
-                .enable_all()
-                .build()
-                .unwrap();
-            runtime.block_on(async {
-                loop {
-                    let latest_transaction_index = {
-                        let db = self.db.lock().await;
-                        u64::from_str(
-                            &db.get(TRANSACTION_INDEX_KEY)
-                                .await
-                                .expect("Couldn't get transaction index"),
-                        )
-                        .expect("Couldn't convert transaction index")
-                    };
-                    let row = crate::inclusion_structure::get_row_for_position(
-                        latest_transaction_index,
-                        INCLUSION_STRUCTURE_ROWS,
-                        INCLUSION_STRUCTURE_SECTION_LENGTH,
-                    );
-                    // Only include messages in row 0 so the proofs don't get very big
-                    let message_ids: Vec<MessageId> = if row == 0 {
-                        if pending_message_ids.read().await.is_empty() {
-                            sleep(std::time::Duration::from_secs(10)).await;
-                            continue;
-                        }
-                        pending_message_ids
-                            .read()
-                            .await
-                            .clone()
-                            .iter()
-                            // Up to 100 message ids per transaction to stay below 10000 bytes length for faster PoW
-                            // even with 10 in and outputs https://gist.github.com/Wollac/82d211781535ad95d39c7db7ae093204
-                            .take(100)
-                            .cloned()
-                            .collect()
-                    } else {
-                        vec![]
-                    };
+    /// Rebuild the db from the ledger if it got lost or corrupted. Starting from the unspent output on the first
+    /// address, the transactions are followed backwards over their inputs from the previous position until the
+    /// transaction that split the funds. The positions, transactions and the messages and hashes of their inclusion
+    /// data are stored again. Transactions and messages that the ledger doesn't have anymore are requested from the
+    /// `fallback`, e.g. a permanode. The storage needs to be empty. Messages that aren't available anymore and the
+    /// message ids and hashes of transactions with a Merkle root can't be restored, they're listed in the report.
+    pub async fn rebuild_from_ledger(
+        mut storage: Box<dyn Storage>,
+        ledger: Arc<dyn LedgerBackend>,
+        fallback: Option<Arc<dyn LedgerBackend>>,
+        seed: &str,
+        config: ChronistConfig,
+    ) -> Result<(Self, RebuildReport)> {
+        for column in StorageColumn::ALL.iter() {
+            if !storage.records_after(*column, None, 1).await?.is_empty() {
+                return Err(crate::error::Error::InvalidParameter(format!(
+                    "storage for the rebuild isn't empty, it has records in {}",
+                    column.name()
+                )));
+            }
+        }
+        // requests fail over to the fallback
+        let history: Arc<dyn LedgerBackend> = match fallback {
+            Some(fallback) => Arc::new(NodePool::new(vec![ledger.clone(), fallback])?),
+            None => ledger.clone(),
+        };
+        let addresses = history.get_addresses(seed, config.rows as usize).await?;
 
-                    match self.send_transaction(message_ids.clone()).await {
-                        Ok(r) => {
-                            println!("Transaction {} sent {}", latest_transaction_index, r);
-                            // remove message ids that got sent
-                            let mut pending_message_ids = pending_message_ids.write().await;
-                            for message_id in message_ids {
-                                pending_message_ids.remove(&message_id);
+        // the latest transaction has the unspent output on the first address, anyone can send outputs with the
+        // Chronist index to it, so only transactions signed with the seed count
+        let mut current = None;
+        for output_id in history.get_address_outputs(&addresses[0]).await? {
+            let message = history
+                .get_included_message(output_id.transaction_id())
+                .await?;
+            if let Some(inclusion_data) = get_own_inclusion_data(&message, &addresses) {
+                current = Some((message, inclusion_data));
+                break;
+            }
+        }
+
+        // newest first, each transaction uses the output on the first address of the previous position
+        let mut transactions = Vec::new();
+        while let Some((message, inclusion_data)) = current {
+            let mut previous = None;
+            // the positions are counted from the transaction that split the funds, so the chain can't have a gap
+            let mut unavailable_input = None;
+            if let Some(Payload::Transaction(tx)) = message.payload() {
+                let Essence::Regular(essence) = tx.essence();
+                for input in essence.inputs() {
+                    if let Input::Utxo(utxo) = input {
+                        let transaction_id = utxo.output_id().transaction_id();
+                        let input_message = match history.get_included_message(transaction_id).await
+                        {
+                            Ok(input_message) => input_message,
+                            Err(_) => {
+                                unavailable_input = Some(*transaction_id);
+                                continue;
+                            }
+                        };
+                        if let Some(Payload::Transaction(input_tx)) = input_message.payload() {
+                            let Essence::Regular(input_essence) = input_tx.essence();
+                            let output_address = input_essence
+                                .outputs()
+                                .get(utxo.output_id().index() as usize)
+                                .and_then(output_address_and_amount)
+                                .map(|(address, _)| address);
+                            if output_address == Some(addresses[0]) {
+                                previous = Some(input_message);
+                                break;
                             }
                         }
-                        Err(e) => println!("{}", e),
                     }
-                    sleep(std::time::Duration::from_secs(10)).await;
                 }
+            }
+            if let (None, Some(transaction_id)) = (&previous, unavailable_input) {
+                return Err(crate::error::Error::LedgerHistoryGap(transaction_id));
+            }
+            transactions.push((message, inclusion_data));
+            // the transaction that split the funds has no inclusion data
+            current = previous.and_then(|message| {
+                get_own_inclusion_data(&message, &addresses)
+                    .map(|inclusion_data| (message, inclusion_data))
             });
+        }
+        transactions.reverse();
+
+        let mut report = RebuildReport {
+            transactions: transactions.len() as u64,
+            ..RebuildReport::default()
+        };
+        let mut batch = StorageBatch::default();
+        for (index, (transaction_message, inclusion_data)) in transactions.iter().enumerate() {
+            // position 0 doesn't exist
+            let position = index as u64 + 1;
+            let utxo_data = UtxoData {
+                position_index: position,
+                message_id: transaction_message.id().0,
+            };
+            batch.set(StorageKey::Position(position), &utxo_data)?;
+            batch.set(
+                StorageKey::TransactionMessage(transaction_message.id().0),
+                transaction_message,
+            )?;
+
+            for message_id in &inclusion_data.message_ids {
+                match history.get_message(message_id).await {
+                    Ok(message) => {
+                        batch.set(
+                            StorageKey::Message(*message_id),
+                            &MessageWrapper {
+                                inclusion_position: Some(position),
+                                message,
+                            },
+                        )?;
+                        batch.set(StorageKey::IncludedMessageId(*message_id), &position)?;
+                    }
+                    Err(_) => report.missing_messages.push(*message_id),
+                }
+            }
+            for hash in &inclusion_data.hashes {
+                batch.set(
+                    StorageKey::ContentHash(*hash),
+                    &HashWrapper {
+                        inclusion_position: Some(position),
+                        hash: *hash,
+                    },
+                )?;
+            }
+            if inclusion_data.merkle_root.is_some() {
+                report.merkle_root_positions.push(position);
+            }
+        }
+
+        // without transactions the funds may not be split yet, then it's done when the Chronist is created
+        if !transactions.is_empty() {
+            batch.set(StorageKey::ChronistConfig, &config)?;
+            batch.set(
+                StorageKey::TransactionIndex,
+                &(transactions.len() as u64 + 1),
+            )?;
+        }
+        storage.write_batch(batch).await?;
+
+        let chronist = Self::with_storage(storage, ledger, seed, config).await?;
+        Ok((chronist, report))
+    }
+
+    // Chronist that shares all state with this one, for the background tasks
+    #[allow(deprecated)]
+    fn handle(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            ledger: self.ledger.clone(),
+            iota_client: self.iota_client.clone(),
+            pending: self.pending.clone(),
+            config: self.config,
+            seed: self.seed.clone(),
+            sending_transacion: self.sending_transacion.clone(),
+            merkle_tree: self.merkle_tree.clone(),
+            allow_private_callback_hosts: self.allow_private_callback_hosts.clone(),
+            events: self.events.clone(),
+            sender: self.sender.clone(),
+            _sender_guard: None,
+        }
+    }
+
+    /// Commit to the message ids and hashes of a transaction with a Merkle root instead of listing them, so a
+    /// transaction can include many more of them. The setting is stored in the db and kept after a restart
+    pub async fn use_merkle_tree(&self, enabled: bool) -> Result<()> {
+        self.db
+            .lock()
+            .await
+            .set(&StorageKey::MerkleTreeEnabled, &enabled)
+            .await?;
+        self.merkle_tree.store(enabled, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// When the pending message ids and hashes are sent, see `SenderHandle::set_batching_policy`
+    pub fn set_batching_policy(&self, policy: BatchingPolicy) {
+        self.sender.set_batching_policy(policy);
+    }
+
+    /// Control and health of the background task that sends the transactions
+    pub fn sender(&self) -> SenderHandle {
+        self.sender.clone()
+    }
+
+    /// Receive the events of messages and transactions
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ChronistEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit_event(&self, event: ChronistEvent) {
+        // only fails if there are no subscribers
+        let _ = self.events.send(event);
+    }
+
+    // Sends the transactions in its own thread, it's controlled with the SenderHandle
+    fn start_sending_transactions(self, sent: SentEntries) {
+        let sender = self.sender.clone();
+        let finished = sender.finished_channel();
+        std::thread::spawn(move || {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let runtime = tokio::runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .build()
+                    .map_err(|e| format!("Couldn't start the sender runtime: {}", e))?;
+                runtime.block_on(self.send_transactions(sent));
+                Ok::<(), String>(())
+            }));
+            let error = match result {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e),
+                Err(_) => Some("Sender crashed".to_string()),
+            };
+            if let Some(e) = &error {
+                println!("{}", e);
+            }
+            sender.record_stop(error);
+            // only fails if nobody waits for the shutdown
+            let _ = finished.send(());
         });
     }
-    async fn send_transaction(&self, message_ids: Vec<MessageId>) -> Result<MessageId> {
+
+    async fn send_transactions(self, mut sent: SentEntries) {
+        let webhooks = tokio::spawn(self.handle().deliver_webhooks());
+        // when the first of the currently pending message ids and hashes was seen, for the max latency
+        let mut pending_since: Option<Instant> = None;
+        while self.sender.wait_while_paused().await {
+            let policy = self.sender.batching_policy();
+            // a transaction of a failed attempt could have been posted despite the error, or its built transaction is
+            // posted again
+            match self.recover_pending_transaction().await {
+                Ok(recovered) => {
+                    for (position, (message_ids, hashes)) in recovered {
+                        self.remove_sent(&mut sent, position, message_ids, hashes)
+                            .await;
+                    }
+                }
+                Err(e) => {
+                    println!("Couldn't recover pending transaction: {}", e);
+                    self.sender.record_failure(&e);
+                    self.sender
+                        .sleep(policy.backoff(self.sender.health().consecutive_failures))
+                        .await;
+                    continue;
+                }
+            }
+            // the previous transactions, also the ones stored before a restart, are confirmed before the next one is
+            // sent
+            if let Err(e) = self.confirm_transactions(&mut sent).await {
+                println!("Couldn't confirm transactions: {}", e);
+                self.sender.record_failure(&e);
+                self.sender
+                    .sleep(policy.backoff(self.sender.health().consecutive_failures))
+                    .await;
+                continue;
+            }
+            let latest_transaction_index = match self
+                .db
+                .lock()
+                .await
+                .get::<u64>(&StorageKey::TransactionIndex)
+                .await
+            {
+                Ok(latest_transaction_index) => latest_transaction_index,
+                Err(e) => {
+                    println!("Couldn't get transaction index: {}", e);
+                    self.sender.record_failure(&e);
+                    self.sender
+                        .sleep(policy.backoff(self.sender.health().consecutive_failures))
+                        .await;
+                    continue;
+                }
+            };
+            let row = crate::inclusion_structure::get_row_for_position(
+                latest_transaction_index,
+                self.config.rows,
+                self.config.section_length,
+            );
+            // Only include messages in row 0 so the proofs don't get very big
+            let (message_ids, hashes): (Vec<MessageId>, Vec<ContentHash>) = if row == 0 {
+                let pending = self.pending.read().await.len();
+                if pending == 0 {
+                    pending_since = None;
+                    self.sender.wait_for_pending(None).await;
+                    continue;
+                }
+                // wait until the batch is full or the first of them waited for the max latency
+                let waiting = pending_since.get_or_insert_with(Instant::now).elapsed();
+                if pending < policy.batch_size && waiting < policy.max_latency {
+                    self.sender
+                        .wait_for_pending(Some(policy.max_latency - waiting))
+                        .await;
+                    continue;
+                }
+                // Up to 100 message ids per transaction to stay below 10000 bytes length for faster PoW
+                // even with 10 in and outputs https://gist.github.com/Wollac/82d211781535ad95d39c7db7ae093204
+                // With a Merkle tree only the root is in the transaction
+                let max_entries = if self.merkle_tree.load(Ordering::SeqCst) {
+                    MAX_MERKLE_TREE_LEAVES
+                } else {
+                    100
+                };
+                // in the order of the queue, so high priority ones are sent first and message ids and hashes can't
+                // starve each other
+                let mut message_ids = Vec::new();
+                let mut hashes = Vec::new();
+                let mut entries = 0;
+                for item in self.pending.read().await.iter() {
+                    let size = match item {
+                        PendingItem::MessageId(_) => 1,
+                        PendingItem::Hash(_) => HASH_ENTRY_SIZE,
+                    };
+                    if entries + size > max_entries {
+                        break;
+                    }
+                    entries += size;
+                    match item {
+                        PendingItem::MessageId(message_id) => message_ids.push(*message_id),
+                        PendingItem::Hash(hash) => hashes.push(*hash),
+                    }
+                }
+                (message_ids, hashes)
+            } else {
+                (vec![], vec![])
+            };
+
+            match self
+                .send_transaction(message_ids.clone(), hashes.clone())
+                .await
+            {
+                Ok(r) => {
+                    println!("Transaction {} sent {}", latest_transaction_index, r);
+                    self.sender.record_transaction();
+                    if row == 0 {
+                        pending_since = None;
+                    }
+                    self.remove_sent(&mut sent, latest_transaction_index, message_ids, hashes)
+                        .await;
+                }
+                Err(e) => {
+                    println!("Transaction {} failed: {}", latest_transaction_index, e);
+                    self.sender.record_failure(&e);
+                    self.emit_event(ChronistEvent::TransactionFailed {
+                        position: latest_transaction_index,
+                        error: e.to_string(),
+                    });
+                    // back off so a failing node doesn't get flooded with transactions
+                    self.sender
+                        .sleep(policy.backoff(self.sender.health().consecutive_failures))
+                        .await;
+                }
+            }
+        }
+        // the webhook deliveries also stop with the sender
+        if let Err(e) = webhooks.await {
+            println!("Webhook deliveries stopped with an error: {}", e);
+        }
+        println!("Sender stopped");
+    }
+
+    // Remove the message ids and hashes of a stored transaction from the pending ones, their stored entries got
+    // deleted with the transaction
+    async fn remove_sent(
+        &self,
+        sent: &mut SentEntries,
+        position: u64,
+        message_ids: Vec<MessageId>,
+        hashes: Vec<ContentHash>,
+    ) {
+        let mut pending = self.pending.write().await;
+        for message_id in &message_ids {
+            pending.remove(message_id);
+        }
+        for hash in &hashes {
+            pending.remove(hash);
+        }
+        drop(pending);
+        sent.insert(position, (message_ids, hashes));
+    }
+
+    async fn send_transaction(
+        &self,
+        message_ids: Vec<MessageId>,
+        hashes: Vec<ContentHash>,
+    ) -> Result<MessageId> {
         // lock sending_transaction so no conflicts are generated
-        self.sending_transacion.lock().await;
+        let _sending_transaction = self.sending_transacion.lock().await;
 
-        let mut latest_transaction_index = {
+        let latest_transaction_index = {
             let db = self.db.lock().await;
-            u64::from_str(&db.get(TRANSACTION_INDEX_KEY).await?)?
+            db.get::<u64>(&StorageKey::TransactionIndex).await?
         };
 
-        let inclusion_data = InclusionData {
-            time: Utc::now(),
+        let merkle_tree = if self.merkle_tree.load(Ordering::SeqCst)
+            && !(message_ids.is_empty() && hashes.is_empty())
+        {
+            let leaves = message_ids
+                .iter()
+                .map(message_id_leaf)
+                .chain(hashes.iter().map(content_hash_leaf))
+                .collect();
+            Some(MerkleTree::new(leaves)?)
+        } else {
+            None
+        };
+        let inclusion_data = match &merkle_tree {
+            Some(merkle_tree) => InclusionData {
+                version: INCLUSION_DATA_VERSION,
+                time: Utc::now(),
+                message_ids: Vec::new(),
+                hashes: Vec::new(),
+                merkle_root: Some(merkle_tree.root()),
+            },
+            None => InclusionData {
+                version: INCLUSION_DATA_VERSION,
+                time: Utc::now(),
+                message_ids: message_ids.clone(),
+                hashes: hashes.clone(),
+                merkle_root: None,
+            },
+        };
+
+        let addresses: Vec<Address> = self
+            .ledger
+            .get_addresses(&self.seed, self.config.rows as usize)
+            .await?;
+        let inputs = self
+            .get_inputs_for_position(latest_transaction_index, &addresses)
+            .await?;
+
+        // build the transaction and store it before it gets posted, so it can be recovered if it gets posted, but not
+        // stored
+        let built_message = build_transaction(
+            self.ledger.as_ref(),
+            CHRONIST_INDEX,
+            &serde_json::to_string(&inclusion_data)?,
+            Some(inputs),
+            &self.seed,
+            latest_transaction_index,
+            &self.config,
+        )
+        .await?;
+        let pending_transaction = PendingTransaction {
+            position: latest_transaction_index,
             message_ids,
+            hashes,
+            merkle_tree,
+            message: built_message.clone(),
         };
+        self.db
+            .lock()
+            .await
+            .set(&StorageKey::PendingTransaction, &pending_transaction)
+            .await?;
+
+        let transaction_message = self.ledger.post_and_get_message(&built_message).await?;
+
+        self.emit_event(ChronistEvent::TransactionSent {
+            position: latest_transaction_index,
+            message_id: transaction_message.id().0,
+        });
 
-        // get inputs
+        self.store_transaction(pending_transaction, &transaction_message)
+            .await?;
+        Ok(transaction_message.id().0)
+    }
+
+    // Wait until the stored transactions after the latest confirmed one are included, emit their events and enqueue
+    // the webhooks of their messages. The confirmed position is stored together with the enqueued deliveries, so after
+    // a crash or a failed confirmation the remaining transactions are confirmed again. `sent` has the message ids and
+    // hashes of the transactions sent or recovered since the start, for the `ProofReady` and `HashProofReady` events
+    async fn confirm_transactions(&self, sent: &mut SentEntries) -> Result<()> {
+        let (confirmed_transaction_index, latest_transaction_index) = {
+            let database = self.db.lock().await;
+            (
+                database
+                    .get::<u64>(&StorageKey::ConfirmedTransactionIndex)
+                    .await?,
+                database.get::<u64>(&StorageKey::TransactionIndex).await?,
+            )
+        };
+        for position in confirmed_transaction_index + 1..latest_transaction_index {
+            let utxo_data: UtxoData = self
+                .db
+                .lock()
+                .await
+                .get(&StorageKey::Position(position))
+                .await?;
+            if let Err(e) = self.ledger.wait_until_included(&utxo_data.message_id).await {
+                self.emit_event(ChronistEvent::TransactionFailed {
+                    position,
+                    error: e.to_string(),
+                });
+                return Err(e);
+            }
+            enqueue_webhooks(&mut *self.db.lock().await, position).await?;
+            self.emit_event(ChronistEvent::TransactionConfirmed {
+                position,
+                message_id: utxo_data.message_id,
+            });
+            let (message_ids, hashes) = sent.remove(&position).unwrap_or_default();
+            for message_id in message_ids {
+                self.emit_event(ChronistEvent::ProofReady { message_id });
+            }
+            for hash in hashes {
+                self.emit_event(ChronistEvent::HashProofReady { hash });
+            }
+        }
+        Ok(())
+    }
+
+    // Get the outputs of the previous transactions that are used as inputs for the transaction at this position
+    async fn get_inputs_for_position(
+        &self,
+        position: u64,
+        addresses: &[Address],
+    ) -> Result<Vec<OutputId>> {
         let input_indexes =
             crate::inclusion_structure::get_previous_indexes_for_each_row_at_position(
-                latest_transaction_index,
-                INCLUSION_STRUCTURE_ROWS,
-                INCLUSION_STRUCTURE_SECTION_LENGTH,
+                position,
+                self.config.rows,
+                self.config.section_length,
             );
 
-        let iota_client = self.iota_client.clone();
-        let addresses: Vec<Address> = iota_client
-            .get_addresses(&Seed::from_bytes(&hex::decode(&self.seed)?))
-            .with_range(0..crate::chronist::INCLUSION_STRUCTURE_ROWS as usize)
-            .get_all_raw()
-            .await?
-            .into_iter()
-            .filter(|a| !a.1)
-            .map(|a| a.0)
-            .collect();
-
         let mut inputs = Vec::new();
         for input in input_indexes {
             let database = self.db.lock().await;
-            let position_data: UtxoData = serde_json::from_str(
-                &database
-                    .get(&format!("{}{}", INCLUSION_INDEX, input.0))
-                    .await?,
-            )?;
-            let message: Message = serde_json::from_str(
-                &database
-                    .get(&format!(
-                        "{}{}",
-                        TRANSACTION_MESSAGE_KEY,
-                        position_data.message_id.to_string()
-                    ))
-                    .await?,
-            )?;
+            let position_data: UtxoData = database.get(&StorageKey::Position(input.0)).await?;
+            let message: Message = database
+                .get(&StorageKey::TransactionMessage(position_data.message_id))
+                .await?;
 
             if let Some(Payload::Transaction(tx)) = message.payload() {
-                let Essence::Regular(essence) = tx.essence();
-                if essence.outputs().len() < input.1 as usize {
-                    return Err(crate::error::Error::UtxoInputNotFound);
-                }
-                // todo return error if output doesn't exists
-                let output_position = essence
-                    .outputs()
-                    .iter()
-                    .position(|output| {
-                        let address = match output {
-                            Output::Treasury(_) => {
-                                panic!("Treasury output is not supported");
-                            }
-                            Output::SignatureLockedSingle(ref r) => match &r.address() {
-                                Address::Ed25519(addr) => Address::Ed25519(*addr),
-                            },
-                            Output::SignatureLockedDustAllowance(ref r) => match &r.address() {
-                                Address::Ed25519(addr) => Address::Ed25519(*addr),
-                            },
-                        };
-                        addresses[input.1 as usize] == address
-                    })
-                    .expect("No output with address");
-                inputs.push(OutputId::new(tx.id(), output_position as u16)?);
+                let address = addresses
+                    .get(input.1 as usize)
+                    .ok_or(crate::error::Error::UtxoOutputNotFound)?;
+                inputs.push(find_output_id(tx, address)?);
             } else {
                 return Err(crate::error::Error::UtxoInputNotFound);
             }
         }
+        Ok(inputs)
+    }
 
-        // Send new transaction with message
-        let transaction_message = send_transaction(
-            &iota_client,
-            CHRONIST_INDEX,
-            &serde_json::to_string(&inclusion_data)?,
-            Some(inputs),
-            &self.seed,
-            latest_transaction_index,
-        )
-        .await?;
-
+    // Store a sent transaction and update the included messages and hashes in a single write batch, so a crash
+    // can't leave the db in an inconsistent state
+    async fn store_transaction(
+        &self,
+        pending_transaction: PendingTransaction,
+        transaction_message: &Message,
+    ) -> Result<()> {
+        let position = pending_transaction.position;
         let utxo_data = UtxoData {
-            position_index: latest_transaction_index,
+            position_index: position,
             message_id: transaction_message.id().0,
         };
 
         let mut database = self.db.lock().await;
+        let mut batch = StorageBatch::default();
         // store new utxo data
-        database
-            .set(
-                &format!("{}{}", INCLUSION_INDEX, latest_transaction_index),
-                serde_json::to_string(&utxo_data)?,
-            )
-            .await?;
+        batch.set(StorageKey::Position(position), &utxo_data)?;
 
         // store new transaction_message
-        database
-            .set(
-                &format!(
-                    "{}{}",
-                    TRANSACTION_MESSAGE_KEY,
-                    transaction_message.id().0.to_string()
-                ),
-                serde_json::to_string(&transaction_message)?,
-            )
-            .await?;
+        batch.set(
+            StorageKey::TransactionMessage(transaction_message.id().0),
+            transaction_message,
+        )?;
+
+        // store the Merkle tree for the audit paths of the proofs
+        if let Some(merkle_tree) = &pending_transaction.merkle_tree {
+            batch.set(StorageKey::MerkleTree(position), merkle_tree)?;
+        }
 
         // store message ids and update inclusion_position
-        let mut message_ids = self.message_ids.write().await;
-        for message_id in inclusion_data.message_ids {
-            message_ids.insert(message_id.to_owned());
+        for message_id in pending_transaction.message_ids.iter() {
+            batch.set(StorageKey::IncludedMessageId(*message_id), &position)?;
+            batch.delete(StorageKey::PendingMessageId(*message_id));
             // update inclusion_position
             let mut message_without_inclusion_position: MessageWrapper =
-                serde_json::from_str(&database.get(&message_id.to_string()).await?)?;
-            message_without_inclusion_position.inclusion_position = Some(latest_transaction_index);
-            database
-                .set(
-                    &message_id.to_string(),
-                    serde_json::to_string(&message_without_inclusion_position)?,
-                )
-                .await?;
+                database.get(&StorageKey::Message(*message_id)).await?;
+            message_without_inclusion_position.inclusion_position = Some(position);
+            batch.set(
+                StorageKey::Message(*message_id),
+                &message_without_inclusion_position,
+            )?;
         }
 
-        database
-            .set(MESSAGE_IDS_KEY, serde_json::to_string(&*message_ids)?)
-            .await?;
+        // update inclusion_position of the hashes
+        for hash in &pending_transaction.hashes {
+            let key = StorageKey::ContentHash(*hash);
+            let mut hash_without_inclusion_position: HashWrapper = database.get(&key).await?;
+            hash_without_inclusion_position.inclusion_position = Some(position);
+            batch.set(key, &hash_without_inclusion_position)?;
+            batch.delete(StorageKey::PendingHash(*hash));
+        }
 
         // update transaction index
-        latest_transaction_index += 1;
-        // store transaction_index
-        database
-            .set(TRANSACTION_INDEX_KEY, latest_transaction_index.to_string())
-            .await?;
+        batch.set(StorageKey::TransactionIndex, &(position + 1))?;
+        batch.delete(StorageKey::PendingTransaction);
 
-        drop(database);
-        let _ = iota_client
-            .retry_until_included(&transaction_message.id().0, None, None)
-            .await?;
-        Ok(transaction_message.id().0)
+        database.write_batch(batch).await
+    }
+
+    // Check if the transaction of a pending transaction got posted before the Chronist stopped and store it. The built
+    // transaction is looked up by its id and posted again if its inputs are still unspent. Returns the message ids and
+    // hashes of a recovered transaction by its position, for the events once it's confirmed
+    async fn recover_pending_transaction(&self) -> Result<SentEntries> {
+        let pending_transaction: PendingTransaction = {
+            let mut database = self.db.lock().await;
+            let pending_transaction: PendingTransaction =
+                match database.get(&StorageKey::PendingTransaction).await {
+                    Ok(pending_transaction) => pending_transaction,
+                    Err(crate::error::Error::RecordNotFound) => return Ok(SentEntries::new()),
+                    Err(e) => return Err(e),
+                };
+            let latest_transaction_index: u64 = database.get(&StorageKey::TransactionIndex).await?;
+            // already stored
+            if pending_transaction.position != latest_transaction_index {
+                let mut batch = StorageBatch::default();
+                batch.delete(StorageKey::PendingTransaction);
+                database.write_batch(batch).await?;
+                return Ok(SentEntries::new());
+            }
+            pending_transaction
+        };
+
+        let mut transaction_message = match self
+            .ledger
+            .get_message(&pending_transaction.message.id().0)
+            .await
+        {
+            Ok(message) => Some(message),
+            Err(crate::error::Error::MessageNotFound) => None,
+            Err(e) => return Err(e),
+        };
+        if transaction_message.is_none() {
+            let addresses: Vec<Address> = self
+                .ledger
+                .get_addresses(&self.seed, self.config.rows as usize)
+                .await?;
+            let inputs = self
+                .get_inputs_for_position(pending_transaction.position, &addresses)
+                .await?;
+            if let Some(input) = inputs.first() {
+                if self.ledger.is_output_known(input).await {
+                    // not posted
+                    transaction_message = Some(
+                        self.ledger
+                            .post_and_get_message(&pending_transaction.message)
+                            .await?,
+                    );
+                } else {
+                    // the input got spent, so the transaction got posted, but its id changes if the node did the PoW
+                    transaction_message =
+                        self.find_spending_transaction(input, &addresses[0]).await?;
+                }
+            }
+        }
+
+        match transaction_message {
+            Some(transaction_message) => {
+                println!(
+                    "Recovered transaction {} for position {}",
+                    transaction_message.id().0,
+                    pending_transaction.position
+                );
+                let mut sent = SentEntries::new();
+                sent.insert(
+                    pending_transaction.position,
+                    (
+                        pending_transaction.message_ids.clone(),
+                        pending_transaction.hashes.clone(),
+                    ),
+                );
+                self.store_transaction(pending_transaction, &transaction_message)
+                    .await?;
+                Ok(sent)
+            }
+            None => {
+                let mut batch = StorageBatch::default();
+                batch.delete(StorageKey::PendingTransaction);
+                self.db.lock().await.write_batch(batch).await?;
+                Ok(SentEntries::new())
+            }
+        }
+    }
+
+    // Find the transaction that spent an input, every transaction has an output on the first address
+    async fn find_spending_transaction(
+        &self,
+        input: &OutputId,
+        first_address: &Address,
+    ) -> Result<Option<Message>> {
+        for output_id in self.ledger.get_address_outputs(first_address).await? {
+            let message = self
+                .ledger
+                .get_included_message(output_id.transaction_id())
+                .await?;
+            if let Some(Payload::Transaction(tx)) = message.payload() {
+                let Essence::Regular(essence) = tx.essence();
+                if essence.inputs().iter().any(|tx_input| match tx_input {
+                    Input::Utxo(utxo) => utxo.output_id() == input,
+                    _ => false,
+                }) {
+                    return Ok(Some(message));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Allow callback urls with loopback or private hosts, for tests or if only trusted clients can register them
+    pub fn allow_private_callback_hosts(&self, allowed: bool) {
+        self.allow_private_callback_hosts
+            .store(allowed, Ordering::SeqCst);
+    }
+
+    /// Register a url to which the proof gets posted once the message is included and the transaction confirmed.
+    /// Only http(s) urls of public hosts are accepted, see `webhook::validate_callback_url`
+    pub async fn register_callback(&self, message_id: &str, url: &str) -> Result<()> {
+        let msg_id = MessageId::from_str(message_id)?;
+        webhook::validate_callback_url(
+            url,
+            self.allow_private_callback_hosts.load(Ordering::SeqCst),
+        )
+        .await?;
+        let key = StorageKey::Callback(msg_id);
+        let mut database = self.db.lock().await;
+        let mut urls: Vec<String> = match database.get(&key).await {
+            Ok(urls) => urls,
+            Err(crate::error::Error::RecordNotFound) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+        database.set(&key, &urls).await?;
+
+        // the transaction of the message could already be confirmed
+        let confirmed_transaction_index: u64 =
+            database.get(&StorageKey::ConfirmedTransactionIndex).await?;
+        enqueue_webhooks(&mut database, confirmed_transaction_index).await
+    }
+
+    // Post the proofs of the queued deliveries, failed deliveries are retried with an increasing delay
+    async fn deliver_webhooks(self) {
+        while self.sender.sleep(webhook::DELIVERY_INTERVAL).await {
+            let queue = match webhook::load_queue(&*self.db.lock().await).await {
+                Ok(queue) => queue,
+                Err(e) => {
+                    println!("Couldn't load webhook queue: {}", e);
+                    continue;
+                }
+            };
+            let now = Utc::now();
+            let mut results = Vec::new();
+            for delivery in queue.iter().filter(|delivery| delivery.next_attempt <= now) {
+                let result = match self
+                    .get_message_proof(&delivery.message_id.to_string())
+                    .await
+                {
+                    Ok(proof) => {
+                        webhook::post_proof(
+                            &delivery.url,
+                            &proof,
+                            self.allow_private_callback_hosts.load(Ordering::SeqCst),
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = &result {
+                    println!(
+                        "Webhook delivery of {} to {} failed: {}",
+                        delivery.message_id, delivery.url, e
+                    );
+                }
+                results.push((delivery.clone(), result.is_ok()));
+            }
+            if results.is_empty() {
+                continue;
+            }
+
+            // reload the queue, because deliveries could have been added in the meantime
+            let mut database = self.db.lock().await;
+            let mut queue = match webhook::load_queue(&database).await {
+                Ok(queue) => queue,
+                Err(e) => {
+                    println!("Couldn't load webhook queue: {}", e);
+                    continue;
+                }
+            };
+            for (delivery, delivered) in results {
+                queue.retain(|queued| !queued.is_same(&delivery));
+                if !delivered {
+                    match delivery.retry() {
+                        Some(delivery) => queue.push(delivery),
+                        None => println!(
+                            "Dropped webhook delivery of {} to {} after {} attempts",
+                            delivery.message_id,
+                            delivery.url,
+                            webhook::MAX_DELIVERY_ATTEMPTS
+                        ),
+                    }
+                }
+            }
+            if let Err(e) = webhook::store_queue(&mut database, &queue).await {
+                println!("Couldn't store webhook queue: {}", e);
+            }
+        }
     }
 
     pub async fn save_message(&self, message_id: &str) -> Result<()> {
+        self.save_message_with_priority(message_id, Priority::Normal)
+            .await
+    }
+
+    /// Like `save_message`, high priority message ids are sent before older normal priority ones
+    pub async fn save_message_with_priority(
+        &self,
+        message_id: &str,
+        priority: Priority,
+    ) -> Result<()> {
+        match self.save_message_status(message_id, priority).await? {
+            SaveMessageStatus::NotFoundOnNode => Err(crate::error::Error::MessageNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Store messages and add them to the pending messages, returns the status for each message id
+    pub async fn save_messages(
+        &self,
+        message_ids: &[String],
+    ) -> Vec<(String, Result<SaveMessageStatus>)> {
+        self.save_messages_with_priority(message_ids, Priority::Normal)
+            .await
+    }
+
+    /// Like `save_messages` with the priority for all of them
+    pub async fn save_messages_with_priority(
+        &self,
+        message_ids: &[String],
+        priority: Priority,
+    ) -> Vec<(String, Result<SaveMessageStatus>)> {
+        let mut statuses = Vec::with_capacity(message_ids.len());
+        for message_id in message_ids {
+            let status = self.save_message_status(message_id, priority).await;
+            statuses.push((message_id.clone(), status));
+        }
+        statuses
+    }
+
+    /// Store a message and add it to the pending messages if it's not already stored
+    pub async fn save_message_with_status(&self, message_id: &str) -> Result<SaveMessageStatus> {
+        self.save_message_status(message_id, Priority::Normal).await
+    }
+
+    async fn save_message_status(
+        &self,
+        message_id: &str,
+        priority: Priority,
+    ) -> Result<SaveMessageStatus> {
         let msg_id = MessageId::from_str(message_id)?;
+        // pending messages are also stored
+        match self.get_message(message_id).await {
+            Ok(message) => return Ok(stored_message_status(&message)),
+            Err(crate::error::Error::RecordNotFound) => {}
+            Err(e) => return Err(e),
+        }
+        let message = match self.ledger.get_message(&msg_id).await {
+            Ok(message) => message,
+            Err(crate::error::Error::MessageNotFound) => {
+                return Ok(SaveMessageStatus::NotFoundOnNode)
+            }
+            Err(e) => return Err(e),
+        };
+
+        // check again under the lock, the same message id could have been stored while it was requested from the node
+        let mut database = self.db.lock().await;
+        match database
+            .get::<MessageWrapper>(&StorageKey::Message(msg_id))
+            .await
         {
-            if self.message_ids.read().await.contains(&msg_id)
-                || self.pending_message_ids.read().await.contains(&msg_id)
+            Ok(message) => return Ok(stored_message_status(&message)),
+            Err(crate::error::Error::RecordNotFound) => {}
+            Err(e) => return Err(e),
+        }
+        // store the new message together with its entry in the pending queue, so it gets included in transactions
+        let mut batch = StorageBatch::default();
+        batch.set(
+            StorageKey::Message(msg_id),
+            &MessageWrapper {
+                inclusion_position: None,
+                message,
+            },
+        )?;
+        let mut pending = self.pending.write().await;
+        pending.insert(msg_id, priority);
+        if let Some(entry) = pending.entry(msg_id) {
+            batch.set(StorageKey::PendingMessageId(msg_id), &entry)?;
+        }
+        if let Err(e) = database.write_batch(batch).await {
+            pending.remove(msg_id);
+            return Err(e);
+        }
+        let pending_count = pending.len();
+        drop(pending);
+        drop(database);
+        self.sender.pending_added(pending_count);
+        self.emit_event(ChronistEvent::MessageAccepted { message_id: msg_id });
+
+        Ok(SaveMessageStatus::Accepted)
+    }
+
+    /// Store a hash so it gets included in the next transactions, returns the status like for messages
+    pub async fn save_hash(&self, hash: ContentHash) -> Result<SaveMessageStatus> {
+        let mut database = self.db.lock().await;
+        match database
+            .get::<HashWrapper>(&StorageKey::ContentHash(hash))
+            .await
+        {
+            Ok(hash_wrapper) => {
+                return Ok(match hash_wrapper.inclusion_position {
+                    Some(inclusion_position) => {
+                        SaveMessageStatus::AlreadyIncluded { inclusion_position }
+                    }
+                    None => SaveMessageStatus::AlreadyPending,
+                })
+            }
+            Err(crate::error::Error::RecordNotFound) => {}
+            Err(e) => return Err(e),
+        }
+
+        let mut batch = StorageBatch::default();
+        batch.set(
+            StorageKey::ContentHash(hash),
+            &HashWrapper {
+                inclusion_position: None,
+                hash,
+            },
+        )?;
+        let mut pending = self.pending.write().await;
+        pending.insert(hash, Priority::Normal);
+        if let Some(entry) = pending.entry(hash) {
+            batch.set(StorageKey::PendingHash(hash), &entry)?;
+        }
+        if let Err(e) = database.write_batch(batch).await {
+            pending.remove(hash);
+            return Err(e);
+        }
+        let pending_count = pending.len();
+        drop(pending);
+        drop(database);
+        self.sender.pending_added(pending_count);
+        self.emit_event(ChronistEvent::HashAccepted { hash });
+
+        Ok(SaveMessageStatus::Accepted)
+    }
+
+    /// Get a stored hash by its hex encoded digest. Without the algorithm the digest needs to be stored for only one
+    /// algorithm
+    pub async fn get_hash(
+        &self,
+        digest: &str,
+        algorithm: Option<HashAlgorithm>,
+    ) -> Result<HashWrapper> {
+        let database = self.db.lock().await;
+        if let Some(algorithm) = algorithm {
+            return database
+                .get(&StorageKey::ContentHash(ContentHash::from_hex(
+                    algorithm, digest,
+                )?))
+                .await;
+        }
+        let mut found = Vec::new();
+        for algorithm in &[HashAlgorithm::Sha256, HashAlgorithm::Blake2b256] {
+            match database
+                .get::<HashWrapper>(&StorageKey::ContentHash(ContentHash::from_hex(
+                    *algorithm, digest,
+                )?))
+                .await
             {
-                println!("Message {} already stored or pending", msg_id);
-                return Ok(());
+                Ok(hash_wrapper) => found.push(hash_wrapper),
+                Err(crate::error::Error::RecordNotFound) => {}
+                Err(e) => return Err(e),
             }
         }
-        let message = self.iota_client.get_message().data(&msg_id).await?;
+        match found.len() {
+            0 => Err(crate::error::Error::RecordNotFound),
+            1 => Ok(found.remove(0)),
+            _ => Err(crate::error::Error::InvalidParameter(format!(
+                "hash {} is stored for multiple algorithms, the algorithm is required",
+                digest
+            ))),
+        }
+    }
 
-        // store new message
-        let mut database = self.db.lock().await;
-        database
-            .set(
-                message_id,
-                serde_json::to_string(&MessageWrapper {
-                    inclusion_position: None,
-                    message,
-                })?,
-            )
+    /// Get a proof that a hash got included in a transaction, the time is in the inclusion data of the first
+    /// transaction and with a milestone anchor also the timestamp of the milestone
+    pub async fn get_hash_proof(
+        &self,
+        digest: &str,
+        algorithm: Option<HashAlgorithm>,
+        with_milestone_anchor: bool,
+    ) -> Result<crate::inclusion_proof::HashInclusionProof> {
+        let hash_wrapper = self.get_hash(digest, algorithm).await?;
+        let inclusion_position = match hash_wrapper.inclusion_position {
+            Some(position) => position,
+            None => return Err(crate::error::Error::InclusionPositionNotSet),
+        };
+        let mut context = self.new_proof_context().await?;
+        let proof_chain = self
+            .build_proof_chain(&mut context, inclusion_position, with_milestone_anchor)
+            .await?;
+        let merkle_proof = self
+            .get_merkle_proof(inclusion_position, &content_hash_leaf(&hash_wrapper.hash))
             .await?;
 
-        // add message_id to pending_message_ids so it gets included in transactions
-        self.pending_message_ids.write().await.insert(msg_id);
-
-        Ok(())
+        Ok(crate::inclusion_proof::HashInclusionProof {
+            version: crate::inclusion_proof::PROOF_VERSION,
+            latest_output_id: proof_chain.latest_output_id,
+            hash: hash_wrapper.hash,
+            transactions: proof_chain.transactions,
+            milestone_anchor: proof_chain.milestone_anchor,
+            merkle_proof,
+        })
     }
 
     pub async fn get_message_proof(
         &self,
         message_id: &str,
     ) -> Result<crate::inclusion_proof::InclusionProof> {
-        let database = self.db.lock().await;
-        let message_wrapper = database.get(message_id).await?;
-        let message_wrapper: MessageWrapper = serde_json::from_str(&message_wrapper)?;
+        self.build_message_proof(message_id, false).await
+    }
+
+    /// Get a proof with the milestone that referenced the last transaction, so it can still be validated after
+    /// the latest output got spent
+    pub async fn get_message_proof_with_milestone(
+        &self,
+        message_id: &str,
+    ) -> Result<crate::inclusion_proof::InclusionProof> {
+        self.build_message_proof(message_id, true).await
+    }
+
+    /// Get a new proof for the message of an old proof, which ends with the current latest output. The transactions of
+    /// the old proof need to be the start of the new proof
+    pub async fn refresh_proof(
+        &self,
+        old_proof: crate::inclusion_proof::InclusionProof,
+    ) -> Result<crate::inclusion_proof::InclusionProof> {
+        let message_id = old_proof.message.id().0.to_string();
+        let new_proof = self
+            .build_message_proof(&message_id, old_proof.milestone_anchor.is_some())
+            .await?;
+
+        if old_proof.transactions.len() > new_proof.transactions.len()
+            || old_proof
+                .transactions
+                .iter()
+                .zip(new_proof.transactions.iter())
+                .any(|(old_tx, new_tx)| old_tx.id() != new_tx.id())
+        {
+            return Err(crate::error::Error::InconsistentProof);
+        }
+        Ok(new_proof)
+    }
+
+    /// Get proofs for multiple messages, the transactions of shared path positions are only loaded once
+    pub async fn get_message_proofs(
+        &self,
+        message_ids: &[String],
+    ) -> Result<Vec<(String, Result<crate::inclusion_proof::InclusionProof>)>> {
+        let mut context = self.new_proof_context().await?;
+        let mut proofs = Vec::with_capacity(message_ids.len());
+        for message_id in message_ids {
+            let proof = self
+                .build_message_proof_with_context(&mut context, message_id, false)
+                .await;
+            proofs.push((message_id.clone(), proof));
+        }
+        Ok(proofs)
+    }
+
+    async fn build_message_proof(
+        &self,
+        message_id: &str,
+        with_milestone_anchor: bool,
+    ) -> Result<crate::inclusion_proof::InclusionProof> {
+        let mut context = self.new_proof_context().await?;
+        let inclusion_proof = self
+            .build_message_proof_with_context(&mut context, message_id, with_milestone_anchor)
+            .await?;
+
+        println!(
+            "Is valid: {}",
+            inclusion_proof.is_valid(self.ledger.as_ref()).await?
+        );
+        Ok(inclusion_proof)
+    }
+
+    async fn new_proof_context(&self) -> Result<ProofContext> {
+        let latest_position = {
+            let database = self.db.lock().await;
+            database.get::<u64>(&StorageKey::TransactionIndex).await? - 1
+        };
+        let addresses: Vec<Address> = self
+            .ledger
+            .get_addresses(&self.seed, self.config.rows as usize)
+            .await?;
+        Ok(ProofContext {
+            latest_position,
+            addresses,
+            path_transactions: HashMap::new(),
+        })
+    }
 
+    async fn build_message_proof_with_context(
+        &self,
+        context: &mut ProofContext,
+        message_id: &str,
+        with_milestone_anchor: bool,
+    ) -> Result<crate::inclusion_proof::InclusionProof> {
+        let message_wrapper = self.get_message(message_id).await?;
         let inclusion_position = match message_wrapper.inclusion_position {
             Some(position) => position,
             None => return Err(crate::error::Error::InclusionPositionNotSet),
         };
+        let proof_chain = self
+            .build_proof_chain(context, inclusion_position, with_milestone_anchor)
+            .await?;
+        let merkle_proof = self
+            .get_merkle_proof(
+                inclusion_position,
+                &message_id_leaf(&message_wrapper.message.id().0),
+            )
+            .await?;
+
+        Ok(crate::inclusion_proof::InclusionProof {
+            version: crate::inclusion_proof::PROOF_VERSION,
+            latest_output_id: proof_chain.latest_output_id,
+            message: message_wrapper.message,
+            transactions: proof_chain.transactions,
+            milestone_anchor: proof_chain.milestone_anchor,
+            merkle_proof,
+        })
+    }
+
+    // Get the audit path if the transaction at the position has a Merkle root
+    async fn get_merkle_proof(
+        &self,
+        inclusion_position: u64,
+        leaf: &MerkleHash,
+    ) -> Result<Option<MerkleProof>> {
+        let merkle_tree = match self
+            .db
+            .lock()
+            .await
+            .get::<MerkleTree>(&StorageKey::MerkleTree(inclusion_position))
+            .await
+        {
+            Ok(merkle_tree) => merkle_tree,
+            Err(crate::error::Error::RecordNotFound) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match merkle_tree.proof(leaf) {
+            Some(merkle_proof) => Ok(Some(merkle_proof)),
+            None => Err(crate::error::Error::InvalidMerkleProof),
+        }
+    }
+
+    // Load the transactions from the inclusion position to the latest position and get the latest output
+    async fn build_proof_chain(
+        &self,
+        context: &mut ProofContext,
+        inclusion_position: u64,
+        with_milestone_anchor: bool,
+    ) -> Result<ProofChain> {
+        let database = self.db.lock().await;
         let input_positions = crate::inclusion_structure::get_path(
             inclusion_position,
-            u64::from_str(&database.get(TRANSACTION_INDEX_KEY).await?)? - 1,
-            INCLUSION_STRUCTURE_ROWS,
-            INCLUSION_STRUCTURE_SECTION_LENGTH,
+            context.latest_position,
+            self.config.rows,
+            self.config.section_length,
         );
 
         let mut path_transactions = Vec::new();
+        let mut latest_transaction_message_id = None;
         for input_positions in input_positions {
-            let position_data: UtxoData = serde_json::from_str(
-                &database
-                    .get(&format!("{}{}", INCLUSION_INDEX, input_positions.0))
-                    .await?,
-            )?;
+            if !context.path_transactions.contains_key(&input_positions.0) {
+                let position_data: UtxoData = database
+                    .get(&StorageKey::Position(input_positions.0))
+                    .await?;
 
-            let position_message: Message = serde_json::from_str(
-                &database
-                    .get(&format!(
-                        "{}{}",
-                        TRANSACTION_MESSAGE_KEY,
-                        position_data.message_id.to_string(),
-                    ))
-                    .await?,
-            )?;
-            let path_tx = match position_message.payload() {
-                Some(Payload::Transaction(tx)) => tx,
-                _ => return Err(crate::error::Error::NoTransactionPayload),
-            };
-            path_transactions.push(*path_tx.clone());
+                let position_message: Message = database
+                    .get(&StorageKey::TransactionMessage(position_data.message_id))
+                    .await?;
+                let path_tx = match position_message.payload() {
+                    Some(Payload::Transaction(tx)) => tx,
+                    _ => return Err(crate::error::Error::NoTransactionPayload),
+                };
+                context.path_transactions.insert(
+                    input_positions.0,
+                    (position_data.message_id, *path_tx.clone()),
+                );
+            }
+            let (transaction_message_id, path_tx) = &context.path_transactions[&input_positions.0];
+            path_transactions.push(path_tx.clone());
+            latest_transaction_message_id = Some(*transaction_message_id);
         }
         drop(database);
         let latest_output_id = {
             let tx = path_transactions
                 .last()
-                .expect("No transactions for proof available ");
+                .ok_or(crate::error::Error::EmptyProofPath)?;
             let Essence::Regular(essence) = tx.essence();
 
-            let addresses: Vec<Address> = self
-                .iota_client
-                .get_addresses(&Seed::from_bytes(&hex::decode(&self.seed)?))
-                .with_range(0..crate::chronist::INCLUSION_STRUCTURE_ROWS as usize)
-                .get_all_raw()
-                .await?
-                .into_iter()
-                .filter(|a| !a.1)
-                .map(|a| a.0)
-                .collect();
             // Get output id from with highest address/row index because they will stay valid the longest time
-            let output_position = essence
+            let address = essence
                 .outputs()
-                .iter()
-                .position(|output| {
-                    let address = match output {
-                        Output::Treasury(_) => {
-                            panic!("Treasury output is not supported");
-                        }
-                        Output::SignatureLockedSingle(ref r) => match &r.address() {
-                            Address::Ed25519(addr) => Address::Ed25519(*addr),
-                        },
-                        Output::SignatureLockedDustAllowance(ref r) => match &r.address() {
-                            Address::Ed25519(addr) => Address::Ed25519(*addr),
-                        },
-                    };
-                    addresses[essence.outputs().len() - 1] == address
-                })
-                .expect("No output with address");
+                .len()
+                .checked_sub(1)
+                .and_then(|row| context.addresses.get(row))
+                .ok_or(crate::error::Error::UtxoOutputNotFound)?;
+            find_output_id(tx, address)?
+        };
 
-            OutputId::new(tx.id(), output_position as u16)?
+        let milestone_anchor = match (with_milestone_anchor, latest_transaction_message_id) {
+            (true, Some(latest_transaction_message_id)) => Some(
+                crate::iota_api::get_milestone_anchor(
+                    self.ledger.as_ref(),
+                    &latest_transaction_message_id,
+                )
+                .await?,
+            ),
+            _ => None,
         };
 
-        let inclusion_proof = crate::inclusion_proof::InclusionProof {
+        Ok(ProofChain {
             // get output id with the highest index, because that will stay the longest time available
             latest_output_id,
-            message: message_wrapper.message,
             transactions: path_transactions,
-        };
+            milestone_anchor,
+        })
+    }
 
-        println!(
-            "Is valid: {}",
-            inclusion_proof.is_valid(&self.iota_client).await?
-        );
-        Ok(inclusion_proof)
+    /// Get the config of the inclusion structure
+    pub fn config(&self) -> ChronistConfig {
+        self.config
     }
 
     pub async fn get_message(&self, message_id: &str) -> Result<MessageWrapper> {
-        let message = self.db.lock().await.get(message_id).await?;
-        let message: MessageWrapper = serde_json::from_str(&message)?;
-        Ok(message)
+        let message_id = MessageId::from_str(message_id)?;
+        self.db
+            .lock()
+            .await
+            .get(&StorageKey::Message(message_id))
+            .await
     }
 
-    pub async fn get_message_ids(&self) -> Result<Vec<String>> {
-        let message_ids = self.db.lock().await.get(MESSAGE_IDS_KEY).await?;
-        let message_ids: Vec<String> = serde_json::from_str(&message_ids)?;
-        Ok(message_ids)
+    /// Get the place of a message id in the queue of pending message ids and hashes, message ids at a position below
+    /// the max entries of a transaction are included in the next transaction in row 0
+    pub async fn get_queue_position(&self, message_id: &str) -> Result<QueuePosition> {
+        let message_id = MessageId::from_str(message_id)?;
+        let pending = self.pending.read().await;
+        match (pending.position(message_id), pending.priority(message_id)) {
+            (Some(position), Some(priority)) => Ok(QueuePosition {
+                message_id,
+                priority,
+                position,
+                pending: pending.len(),
+            }),
+            _ => Err(crate::error::Error::MessageNotPending),
+        }
+    }
+
+    /// Get up to `limit` ids of included messages, ordered by their bytes and starting after the `cursor` message id
+    pub async fn get_message_ids(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<MessageIdsPage> {
+        // an empty page couldn't tell if there are more message ids
+        if limit == 0 {
+            return Err(crate::error::Error::InvalidParameter(
+                "limit needs to be > 0".to_string(),
+            ));
+        }
+        let cursor = match cursor {
+            Some(cursor) => {
+                Some(StorageKey::IncludedMessageId(MessageId::from_str(cursor)?).to_bytes())
+            }
+            None => None,
+        };
+        // one more to know if there is a next page
+        let records = self
+            .db
+            .lock()
+            .await
+            .iter_after::<u64>(
+                StorageColumn::IncludedMessageIds,
+                cursor.as_deref(),
+                limit.saturating_add(1),
+            )
+            .await?;
+        let mut message_ids = records
+            .iter()
+            .map(|(key, _)| decode_message_id(key))
+            .collect::<Result<Vec<MessageId>>>()?;
+        let next_cursor = if message_ids.len() > limit {
+            message_ids.truncate(limit);
+            message_ids.last().copied()
+        } else {
+            None
+        };
+        Ok(MessageIdsPage {
+            message_ids,
+            next_cursor,
+        })
+    }
+}
+
+// Move the callbacks of the messages included up to the confirmed position into the webhook queue and store the
+// confirmed position, in a single write batch so no callback gets lost or delivered twice
+async fn enqueue_webhooks(
+    database: &mut dyn Storage,
+    confirmed_transaction_index: u64,
+) -> Result<()> {
+    let mut queue = webhook::load_queue(database).await?;
+    let mut batch = StorageBatch::default();
+    let mut after: Option<Vec<u8>> = None;
+    loop {
+        let callbacks = database
+            .iter_prefix::<Vec<String>>(
+                StorageColumn::Metadata,
+                StorageKey::callback_prefix(),
+                after.as_deref(),
+                PAGE_SIZE,
+            )
+            .await?;
+        after = match callbacks.last() {
+            Some((key, _)) => Some(key.clone()),
+            None => break,
+        };
+        for (key, urls) in callbacks {
+            let message_id = match StorageKey::callback_message_id(&key) {
+                Some(message_id) => message_id,
+                None => continue,
+            };
+            let message: MessageWrapper = match database.get(&StorageKey::Message(message_id)).await
+            {
+                Ok(message) => message,
+                Err(crate::error::Error::RecordNotFound) => continue,
+                Err(e) => return Err(e),
+            };
+            match message.inclusion_position {
+                Some(position) if position <= confirmed_transaction_index => {}
+                _ => continue,
+            }
+            for url in urls {
+                let delivery = WebhookDelivery::new(message_id, url);
+                if !queue.iter().any(|queued| queued.is_same(&delivery)) {
+                    queue.push(delivery);
+                }
+            }
+            batch.delete(StorageKey::Callback(message_id));
+        }
+    }
+    batch.set(StorageKey::WebhookQueue, &queue)?;
+    batch.set(
+        StorageKey::ConfirmedTransactionIndex,
+        &confirmed_transaction_index,
+    )?;
+    database.write_batch(batch).await
+}
+
+// Status of a message id that's already stored
+fn stored_message_status(message: &MessageWrapper) -> SaveMessageStatus {
+    match message.inclusion_position {
+        Some(inclusion_position) => SaveMessageStatus::AlreadyIncluded { inclusion_position },
+        None => SaveMessageStatus::AlreadyPending,
+    }
+}
+
+// Dbs created before the message ids were stored individually have a list of them, which gets replaced
+async fn migrate_message_ids(database: &mut dyn Storage) -> Result<()> {
+    let message_ids: Vec<MessageId> = match database.get(&StorageKey::MessageIds).await {
+        Ok(message_ids) => message_ids,
+        Err(crate::error::Error::RecordNotFound) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut batch = StorageBatch::default();
+    for message_id in message_ids {
+        let message_wrapper: MessageWrapper =
+            database.get(&StorageKey::Message(message_id)).await?;
+        if let Some(position) = message_wrapper.inclusion_position {
+            batch.set(StorageKey::IncludedMessageId(message_id), &position)?;
+        }
+    }
+    batch.delete(StorageKey::MessageIds);
+    database.write_batch(batch).await
+}
+
+// Load the pending message ids and hashes from their columns, they're written in the same batch as the messages and
+// hashes and removed in the one that stores their transaction
+async fn load_pending(database: &dyn Storage) -> Result<PendingQueue> {
+    let mut pending = PendingQueue::default();
+    for column in &[
+        StorageColumn::PendingMessageIds,
+        StorageColumn::PendingHashes,
+    ] {
+        let mut after: Option<Vec<u8>> = None;
+        loop {
+            let entries = database
+                .iter_prefix::<PendingEntry>(*column, &[], after.as_deref(), PAGE_SIZE)
+                .await?;
+            after = match entries.last() {
+                Some((key, _)) => Some(key.clone()),
+                None => break,
+            };
+            for (_, entry) in entries {
+                pending.insert_entry(entry);
+            }
+        }
+    }
+    Ok(pending)
+}
+
+// Get the inclusion data of a transaction with the Chronist index that is signed by the addresses, None for other
+// messages
+fn get_own_inclusion_data(message: &Message, addresses: &[Address]) -> Option<InclusionData> {
+    match message.payload() {
+        Some(Payload::Transaction(tx)) if crate::validation::is_signed_by(tx, addresses) => {
+            let Essence::Regular(essence) = tx.essence();
+            match essence.payload() {
+                Some(Payload::Indexation(indexation_payload))
+                    if indexation_payload.index() == CHRONIST_INDEX.as_bytes() =>
+                {
+                    serde_json::from_slice(indexation_payload.data()).ok()
+                }
+                _ => None,
+            }
+        }
+        _ => None,
     }
 }
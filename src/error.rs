@@ -1,4 +1,6 @@
 pub type Result<T, E = Error> = anyhow::Result<T, E>;
+use crate::chronist::ChronistConfig;
+use iota_client::bee_message::prelude::TransactionId;
 use warp::reject::Reject;
 
 /// Error of the tangleproof lib
@@ -11,14 +13,24 @@ pub enum Error {
     IotaClientError(#[from] iota_client::Error),
     #[error("Bee_message Error")]
     BeeMessageError(#[from] iota_client::bee_message::Error),
+    #[error("Crypto Error")]
+    CryptoError(#[from] iota_client::crypto::Error),
     #[error("Bee_rest_api Error")]
     BeeRestApiError(#[from] iota_client::bee_rest_api::types::error::Error),
     #[error("Hex Error")]
     HexError(#[from] hex::FromHexError),
+    #[error("Reqwest Error: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+    #[cfg(feature = "rocksdb")]
     #[error("Rocksdb Error")]
     RocksdbError(#[from] rocksdb::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("Sqlite Error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
     #[error("ParseIntError")]
     ParseIntError(#[from] std::num::ParseIntError),
+    #[error("TryFromIntError")]
+    TryFromIntError(#[from] std::num::TryFromIntError),
     #[error("FromUtf8Error")]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
     #[error("Parameter is invalid:{0}")]
@@ -37,16 +49,58 @@ pub enum Error {
     NoIndexationPayload,
     #[error("Message id is not in the transaction")]
     MessageIdNotInTransaction,
+    #[error("Hash is not in the transaction")]
+    HashNotInTransaction,
+    #[error("Merkle audit path doesn't lead to the root in the transaction")]
+    InvalidMerkleProof,
+    #[error("Invalid unlock block for input {0}")]
+    InvalidUnlockBlock(usize),
+    #[error("Invalid signature for input {0}")]
+    InvalidSignature(usize),
+    #[error("Input {0} isn't unlocked by the owner of the consumed output")]
+    InvalidInputOwner(usize),
     #[error("Record not found in db")]
     RecordNotFound,
     #[error("Utxo input not found in message")]
     UtxoInputNotFound,
     #[error("Utxo output not found in message")]
     UtxoOutputNotFound,
+    #[error("Unsupported output type {0}")]
+    UnsupportedOutputType(&'static str),
+    #[error("No transactions for the proof path")]
+    EmptyProofPath,
+    #[error("Message not found in ledger")]
+    MessageNotFound,
+    #[error("Output not found in ledger")]
+    OutputNotFound,
     #[error("Not enough funds")]
     NotEnoughFunds,
+    #[error("Transaction {0} of the chain isn't available on the ledger")]
+    LedgerHistoryGap(TransactionId),
+    #[error("Simulated node failure: {0}")]
+    SimulatedNodeFailure(&'static str),
+    #[error("Unsupported proof version {0}")]
+    UnsupportedProofVersion(u8),
+    #[error("Unsupported inclusion data version {0}")]
+    UnsupportedInclusionDataVersion(u8),
+    #[error("Invalid binary proof: {0}")]
+    InvalidBinaryProof(String),
+    #[error("Proof doesn't match the stored transactions")]
+    InconsistentProof,
+    #[error("Message isn't referenced by a milestone yet")]
+    NotReferencedByMilestone,
+    #[error("Invalid milestone anchor: {0}")]
+    InvalidMilestoneAnchor(String),
+    #[error("Invalid callback url: {0}")]
+    InvalidCallbackUrl(String),
+    #[error("Webhook responded with status {0}")]
+    WebhookDeliveryFailed(u16),
     #[error("Inclusion position not set")]
     InclusionPositionNotSet,
+    #[error("Message isn't pending")]
+    MessageNotPending,
+    #[error("Config {1:?} doesn't match the config {0:?} the db was created with")]
+    ConfigMismatch(ChronistConfig, ChronistConfig),
 }
 
 impl Reject for Error {}
@@ -0,0 +1,51 @@
+use crate::chronist::ContentHash;
+use iota_client::bee_message::MessageId;
+use serde::{Deserialize, Serialize};
+
+/// Amount of events that are buffered for slow subscribers before they miss events
+pub(crate) const EVENT_CHANNEL_CAPACITY: usize = 1000;
+
+/// Activity of the Chronist, can be received with `Chronist::subscribe_events`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ChronistEvent {
+    /// Message got stored and will be included in the next transactions
+    #[serde(rename_all = "camelCase")]
+    MessageAccepted { message_id: MessageId },
+    /// Hash got stored and will be included in the next transactions
+    HashAccepted { hash: ContentHash },
+    /// Transaction for a position of the inclusion structure got sent
+    #[serde(rename_all = "camelCase")]
+    TransactionSent {
+        position: u64,
+        message_id: MessageId,
+    },
+    /// Sending or confirming the transaction for a position failed, the sender continues with the next attempt
+    TransactionFailed { position: u64, error: String },
+    /// Transaction got confirmed, its output can now be used for proofs
+    #[serde(rename_all = "camelCase")]
+    TransactionConfirmed {
+        position: u64,
+        message_id: MessageId,
+    },
+    /// Proof for a message can be requested
+    #[serde(rename_all = "camelCase")]
+    ProofReady { message_id: MessageId },
+    /// Proof for a hash can be requested
+    HashProofReady { hash: ContentHash },
+}
+
+impl ChronistEvent {
+    /// Name of the event type
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChronistEvent::MessageAccepted { .. } => "messageAccepted",
+            ChronistEvent::HashAccepted { .. } => "hashAccepted",
+            ChronistEvent::TransactionSent { .. } => "transactionSent",
+            ChronistEvent::TransactionFailed { .. } => "transactionFailed",
+            ChronistEvent::TransactionConfirmed { .. } => "transactionConfirmed",
+            ChronistEvent::ProofReady { .. } => "proofReady",
+            ChronistEvent::HashProofReady { .. } => "hashProofReady",
+        }
+    }
+}
@@ -1,32 +1,90 @@
-use crate::{error::Result, validation::is_valid_proof};
+use crate::{
+    chronist::{ChronistConfig, ContentHash},
+    error::Result,
+    ledger::LedgerBackend,
+    merkle_tree::{MerkleNode, MerkleProof, MerkleSide},
+    validation::{
+        get_hash_proof_verdict, get_proof_verdict, is_valid_hash_proof, is_valid_proof,
+        verify_proof_offline, OfflineVerificationReport, ProofVerdict,
+    },
+};
+use bee_common::packable::Packable;
 use iota_client::{
-    bee_message::prelude::{Message, OutputId, TransactionPayload},
+    bee_message::prelude::{
+        Message, MessageId, OutputId, Payload, TransactionId, TransactionPayload,
+    },
     bee_rest_api::types::dtos::{MessageDto, TransactionPayloadDto},
-    Client,
 };
 use serde::{de::Error, Deserialize, Serialize, Serializer};
-use std::{convert::TryFrom, str::FromStr};
+use std::{
+    convert::{TryFrom, TryInto},
+    str::FromStr,
+};
+
+/// Version of the proof layout, proofs without a version field are version 0.
+/// 0: transactions were stored as complete messages in `transactionMessages`
+/// 1: transactions are stored as transaction payloads and the proof can have a milestone anchor and a Merkle audit
+/// path
+pub const PROOF_VERSION: u8 = 1;
+/// Version of the binary proof format
+pub const PROOF_BINARY_VERSION: u8 = 1;
 
 /// InclusionProof struct which holds the messages and the latest outputid
 #[derive(Debug, Clone)]
 pub struct InclusionProof {
+    /// Version of the proof layout, see [PROOF_VERSION]
+    pub version: u8,
     pub latest_output_id: OutputId,
     pub message: Message,
     pub transactions: Vec<TransactionPayload>,
+    /// Milestone that referenced the last transaction, with it the proof stays valid when the latest output is spent
+    pub milestone_anchor: Option<MilestoneAnchor>,
+    /// Audit path to the Merkle root in the first transaction, if the transaction has a Merkle root
+    pub merkle_proof: Option<MerkleProof>,
+}
+
+/// Referencing info of the message with the last transaction of a proof
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MilestoneAnchor {
+    /// Message id of the message with the last transaction
+    #[serde(rename = "messageId")]
+    pub message_id: MessageId,
+    #[serde(rename = "milestoneIndex")]
+    pub milestone_index: u32,
+    #[serde(rename = "milestoneTimestamp")]
+    pub milestone_timestamp: u64,
 }
 
 #[derive(Serialize, Deserialize)]
 struct InclusionProofDto {
+    #[serde(default)]
+    version: u8,
     #[serde(rename = "latestOutputId")]
     latest_output_id: String,
     message: MessageDto,
-    #[serde(rename = "transactions")]
+    #[serde(rename = "transactions", default)]
     transactions: Vec<TransactionPayloadDto>,
+    // only used by version 0
+    #[serde(rename = "transactionMessages", default, skip_serializing)]
+    transaction_messages: Vec<MessageDto>,
+    #[serde(
+        rename = "milestoneAnchor",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    milestone_anchor: Option<MilestoneAnchor>,
+    #[serde(
+        rename = "merkleProof",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    merkle_proof: Option<MerkleProof>,
 }
 
 impl Serialize for InclusionProof {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         let inclusion_proof = InclusionProofDto {
+            version: self.version,
             latest_output_id: self.latest_output_id.to_string(),
             message: MessageDto::from(&self.message),
             transactions: self
@@ -34,6 +92,9 @@ impl Serialize for InclusionProof {
                 .iter()
                 .map(TransactionPayloadDto::from)
                 .collect(),
+            transaction_messages: Vec::new(),
+            milestone_anchor: self.milestone_anchor.clone(),
+            merkle_proof: self.merkle_proof.clone(),
         };
         inclusion_proof.serialize(serializer)
     }
@@ -42,22 +103,45 @@ impl Serialize for InclusionProof {
 impl<'de> serde::Deserialize<'de> for InclusionProof {
     fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
         let value: InclusionProofDto = InclusionProofDto::deserialize(d)?;
-        let inclusion_proof =
-            InclusionProof {
-                latest_output_id: OutputId::from_str(&value.latest_output_id)
-                    .map_err(D::Error::custom)?,
-                message: Message::try_from(&value.message).map_err(D::Error::custom)?,
-                transactions:
-                    value
-                        .transactions
-                        .iter()
-                        .map(TransactionPayload::try_from)
-                        .collect::<Result<
-                            Vec<TransactionPayload>,
-                            iota_client::bee_rest_api::types::error::Error,
-                        >>()
-                        .map_err(D::Error::custom)?,
-            };
+        let mut transactions =
+            value
+                .transactions
+                .iter()
+                .map(TransactionPayload::try_from)
+                .collect::<Result<
+                    Vec<TransactionPayload>,
+                    iota_client::bee_rest_api::types::error::Error,
+                >>()
+                .map_err(D::Error::custom)?;
+        match value.version {
+            // version 0 proofs had the complete messages of the transactions
+            0 if transactions.is_empty() => {
+                for message_dto in &value.transaction_messages {
+                    let message = Message::try_from(message_dto).map_err(D::Error::custom)?;
+                    match message.payload() {
+                        Some(Payload::Transaction(tx)) => transactions.push(*tx.clone()),
+                        _ => {
+                            return Err(D::Error::custom(crate::error::Error::NoTransactionPayload))
+                        }
+                    }
+                }
+            }
+            0..=PROOF_VERSION => {}
+            version => {
+                return Err(D::Error::custom(
+                    crate::error::Error::UnsupportedProofVersion(version),
+                ))
+            }
+        }
+        let inclusion_proof = InclusionProof {
+            version: value.version,
+            latest_output_id: OutputId::from_str(&value.latest_output_id)
+                .map_err(D::Error::custom)?,
+            message: Message::try_from(&value.message).map_err(D::Error::custom)?,
+            transactions,
+            milestone_anchor: value.milestone_anchor,
+            merkle_proof: value.merkle_proof,
+        };
         Ok(inclusion_proof)
     }
 }
@@ -66,13 +150,321 @@ impl InclusionProof {
     /// Create a new InclusionProof from an existing OutputId and Message
     pub fn new(output: OutputId, message: Message, transactions: Vec<TransactionPayload>) -> Self {
         InclusionProof {
+            version: PROOF_VERSION,
             latest_output_id: output,
             message,
             transactions,
+            milestone_anchor: None,
+            merkle_proof: None,
+        }
+    }
+    /// Add the milestone that referenced the last transaction
+    pub fn with_milestone_anchor(mut self, milestone_anchor: MilestoneAnchor) -> Self {
+        self.milestone_anchor = Some(milestone_anchor);
+        self
+    }
+    /// Verify transaction chain and check if latest output is unspent or the milestone anchor is valid
+    pub async fn is_valid(&self, ledger: &dyn LedgerBackend) -> Result<bool> {
+        is_valid_proof(ledger, &self).await
+    }
+    /// Validate each step of the proof and report which ones failed
+    pub async fn verdict(&self, ledger: &dyn LedgerBackend) -> ProofVerdict {
+        get_proof_verdict(ledger, self).await
+    }
+    /// Verify everything that doesn't require a node, that the latest output is unspent stays unverified
+    pub fn verify_offline(&self) -> OfflineVerificationReport {
+        verify_proof_offline(self)
+    }
+}
+
+/// Proof that a hash got included in a transaction, like the InclusionProof but for a hash instead of a message
+#[derive(Debug, Clone)]
+pub struct HashInclusionProof {
+    /// Version of the proof layout, see [PROOF_VERSION]
+    pub version: u8,
+    pub latest_output_id: OutputId,
+    pub hash: ContentHash,
+    pub transactions: Vec<TransactionPayload>,
+    /// Milestone that referenced the last transaction, with it the proof stays valid when the latest output is spent
+    pub milestone_anchor: Option<MilestoneAnchor>,
+    /// Audit path to the Merkle root in the first transaction, if the transaction has a Merkle root
+    pub merkle_proof: Option<MerkleProof>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HashInclusionProofDto {
+    version: u8,
+    #[serde(rename = "latestOutputId")]
+    latest_output_id: String,
+    hash: ContentHash,
+    transactions: Vec<TransactionPayloadDto>,
+    #[serde(
+        rename = "milestoneAnchor",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    milestone_anchor: Option<MilestoneAnchor>,
+    #[serde(
+        rename = "merkleProof",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    merkle_proof: Option<MerkleProof>,
+}
+
+impl Serialize for HashInclusionProof {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hash_inclusion_proof = HashInclusionProofDto {
+            version: self.version,
+            latest_output_id: self.latest_output_id.to_string(),
+            hash: self.hash,
+            transactions: self
+                .transactions
+                .iter()
+                .map(TransactionPayloadDto::from)
+                .collect(),
+            milestone_anchor: self.milestone_anchor.clone(),
+            merkle_proof: self.merkle_proof.clone(),
+        };
+        hash_inclusion_proof.serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for HashInclusionProof {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let value: HashInclusionProofDto = HashInclusionProofDto::deserialize(d)?;
+        // hash proofs exist since version 1
+        if value.version == 0 || value.version > PROOF_VERSION {
+            return Err(D::Error::custom(
+                crate::error::Error::UnsupportedProofVersion(value.version),
+            ));
         }
+        let transactions = value
+            .transactions
+            .iter()
+            .map(TransactionPayload::try_from)
+            .collect::<Result<Vec<TransactionPayload>, iota_client::bee_rest_api::types::error::Error>>()
+            .map_err(D::Error::custom)?;
+        Ok(HashInclusionProof {
+            version: value.version,
+            latest_output_id: OutputId::from_str(&value.latest_output_id)
+                .map_err(D::Error::custom)?,
+            hash: value.hash,
+            transactions,
+            milestone_anchor: value.milestone_anchor,
+            merkle_proof: value.merkle_proof,
+        })
+    }
+}
+
+impl HashInclusionProof {
+    /// Verify transaction chain and check if latest output is unspent or the milestone anchor is valid
+    pub async fn is_valid(&self, ledger: &dyn LedgerBackend) -> Result<bool> {
+        is_valid_hash_proof(ledger, self).await
     }
-    /// Verify transaction chain and check if latest output is unspent
-    pub async fn is_valid(&self, client: &Client) -> Result<bool> {
-        is_valid_proof(client, &self).await
+    /// Validate each step of the proof and report which ones failed
+    pub async fn verdict(&self, ledger: &dyn LedgerBackend) -> ProofVerdict {
+        get_hash_proof_verdict(ledger, self).await
+    }
+}
+
+// Binary format, all integers are little endian:
+// version: u8 | rows: u64 | section_length: u64 | amount: u64
+// proof version: u8
+// latest_output_id: transaction id [u8; 32], output index u16
+// message: length u32, packed message
+// transactions: amount u16, for each: length u32, packed transaction payload
+// milestone_anchor: flag u8, if 1: message id [u8; 32], milestone index u32, milestone timestamp u64
+// merkle_proof: flag u8, if 1: amount u16, for each: side u8 (0 left, 1 right), hash [u8; 32]
+impl InclusionProof {
+    /// Encode the proof in the compact binary format, the config is stored in the header
+    pub fn to_bytes(&self, config: &ChronistConfig) -> Result<Vec<u8>> {
+        let mut bytes = vec![PROOF_BINARY_VERSION];
+        bytes.extend_from_slice(&config.rows.to_le_bytes());
+        bytes.extend_from_slice(&config.section_length.to_le_bytes());
+        bytes.extend_from_slice(&config.amount.to_le_bytes());
+        bytes.push(self.version);
+
+        bytes.extend_from_slice(self.latest_output_id.transaction_id().as_ref());
+        bytes.extend_from_slice(&self.latest_output_id.index().to_le_bytes());
+
+        write_with_length(&mut bytes, &self.message.pack_new())?;
+        bytes.extend_from_slice(&u16::try_from(self.transactions.len())?.to_le_bytes());
+        for transaction in &self.transactions {
+            write_with_length(&mut bytes, &transaction.pack_new())?;
+        }
+
+        match &self.milestone_anchor {
+            Some(milestone_anchor) => {
+                bytes.push(1);
+                bytes.extend_from_slice(milestone_anchor.message_id.as_ref());
+                bytes.extend_from_slice(&milestone_anchor.milestone_index.to_le_bytes());
+                bytes.extend_from_slice(&milestone_anchor.milestone_timestamp.to_le_bytes());
+            }
+            None => bytes.push(0),
+        }
+
+        match &self.merkle_proof {
+            Some(merkle_proof) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&u16::try_from(merkle_proof.path.len())?.to_le_bytes());
+                for node in &merkle_proof.path {
+                    bytes.push(match node.side {
+                        MerkleSide::Left => 0,
+                        MerkleSide::Right => 1,
+                    });
+                    bytes.extend_from_slice(&node.hash);
+                }
+            }
+            None => bytes.push(0),
+        }
+        Ok(bytes)
+    }
+
+    /// Decode a proof from the compact binary format, returns the proof and the config from the header
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, ChronistConfig)> {
+        let mut reader = ByteReader { bytes };
+        let binary_version = reader.read_u8()?;
+        if binary_version != PROOF_BINARY_VERSION {
+            return Err(crate::error::Error::InvalidBinaryProof(format!(
+                "unsupported version {}",
+                binary_version
+            )));
+        }
+        let config = ChronistConfig {
+            rows: reader.read_u64()?,
+            section_length: reader.read_u64()?,
+            amount: reader.read_u64()?,
+        };
+        // the positions of the proof are computed with the config
+        config.validate()?;
+        let version = reader.read_u8()?;
+        if version > PROOF_VERSION {
+            return Err(crate::error::Error::UnsupportedProofVersion(version));
+        }
+
+        let transaction_id = TransactionId::new(reader.read_array()?);
+        let latest_output_id = OutputId::new(transaction_id, reader.read_u16()?)?;
+
+        let message = Message::unpack(&mut reader.read_with_length()?)?;
+        let transactions_len = reader.read_u16()?;
+        let mut transactions = Vec::with_capacity(transactions_len as usize);
+        for _ in 0..transactions_len {
+            transactions.push(TransactionPayload::unpack(&mut reader.read_with_length()?)?);
+        }
+
+        let milestone_anchor = match reader.read_u8()? {
+            0 => None,
+            1 => Some(MilestoneAnchor {
+                message_id: MessageId::new(reader.read_array()?),
+                milestone_index: reader.read_u32()?,
+                milestone_timestamp: reader.read_u64()?,
+            }),
+            flag => {
+                return Err(crate::error::Error::InvalidBinaryProof(format!(
+                    "invalid milestone anchor flag {}",
+                    flag
+                )))
+            }
+        };
+        let merkle_proof = match reader.read_u8()? {
+            0 => None,
+            1 => {
+                let path_len = reader.read_u16()?;
+                let mut path = Vec::with_capacity(path_len as usize);
+                for _ in 0..path_len {
+                    let side = match reader.read_u8()? {
+                        0 => MerkleSide::Left,
+                        1 => MerkleSide::Right,
+                        side => {
+                            return Err(crate::error::Error::InvalidBinaryProof(format!(
+                                "invalid merkle node side {}",
+                                side
+                            )))
+                        }
+                    };
+                    path.push(MerkleNode {
+                        side,
+                        hash: reader.read_array()?,
+                    });
+                }
+                Some(MerkleProof { path })
+            }
+            flag => {
+                return Err(crate::error::Error::InvalidBinaryProof(format!(
+                    "invalid merkle proof flag {}",
+                    flag
+                )))
+            }
+        };
+        if !reader.bytes.is_empty() {
+            return Err(crate::error::Error::InvalidBinaryProof(
+                "remaining bytes after proof".to_string(),
+            ));
+        }
+
+        Ok((
+            InclusionProof {
+                version,
+                latest_output_id,
+                message,
+                transactions,
+                milestone_anchor,
+                merkle_proof,
+            },
+            config,
+        ))
+    }
+}
+
+fn write_with_length(bytes: &mut Vec<u8>, data: &[u8]) -> Result<()> {
+    bytes.extend_from_slice(&u32::try_from(data.len())?.to_le_bytes());
+    bytes.extend_from_slice(data);
+    Ok(())
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(crate::error::Error::InvalidBinaryProof(
+                "unexpected end of bytes".to_string(),
+            ));
+        }
+        let (slice, remaining) = self.bytes.split_at(len);
+        self.bytes = remaining;
+        Ok(slice)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        // the length is checked by read_slice
+        Ok(self
+            .read_slice(N)?
+            .try_into()
+            .expect("Slice with wrong length"))
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_with_length(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_u32()?;
+        self.read_slice(len as usize)
     }
 }
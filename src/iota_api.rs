@@ -1,117 +1,158 @@
-use crate::error::Result;
-use iota_client::{
-    api::ClientMessageBuilder,
-    bee_message::prelude::{Message, OutputId, UtxoInput},
-    node::OutputsOptions,
-    Client, Seed,
+use crate::{
+    chronist::ChronistConfig,
+    error::Result,
+    inclusion_proof::MilestoneAnchor,
+    ledger::{output_address_and_amount, LedgerBackend},
 };
+use iota_client::bee_message::prelude::{Message, MessageId, OutputId};
 use tokio::time::sleep;
 
-const IOTA_AMOUNT: u64 = 1_000_000;
-
 /// Function to get an outputid
-pub async fn is_output_known(client: &Client, output_id: &OutputId) -> bool {
-    matches!(client.get_output(&UtxoInput::from(*output_id)).await, Ok(_))
+pub async fn is_output_known(ledger: &dyn LedgerBackend, output_id: &OutputId) -> bool {
+    ledger.is_output_known(output_id).await
+}
+
+/// Function to get the milestone that referenced a message
+pub async fn get_milestone_anchor(
+    ledger: &dyn LedgerBackend,
+    message_id: &MessageId,
+) -> Result<MilestoneAnchor> {
+    let metadata = ledger.get_message_metadata(message_id).await?;
+    let milestone_index = metadata
+        .referenced_by_milestone_index
+        .ok_or(crate::error::Error::NotReferencedByMilestone)?;
+    let milestone = ledger.get_milestone(milestone_index).await?;
+    Ok(MilestoneAnchor {
+        message_id: *message_id,
+        milestone_index,
+        milestone_timestamp: milestone.timestamp,
+    })
 }
 
 /// Function to send a transaction with an indexation payload
 pub async fn send_transaction(
-    client: &Client,
+    ledger: &dyn LedgerBackend,
     indexation_tag: &str,
     data: &str,
     inputs: Option<Vec<OutputId>>,
     seed: &str,
     inclusion_position: u64,
+    config: &ChronistConfig,
 ) -> Result<Message> {
-    let seed = Seed::from_bytes(&hex::decode(seed)?);
-
-    let addresses = client
-        .get_addresses(&seed)
-        .with_range(0..crate::chronist::INCLUSION_STRUCTURE_ROWS as usize)
-        .finish()
-        .await?;
+    let message = build_transaction(
+        ledger,
+        indexation_tag,
+        data,
+        inputs,
+        seed,
+        inclusion_position,
+        config,
+    )
+    .await?;
+    ledger.post_and_get_message(&message).await
+}
 
-    let mut message_builder = client.message().with_seed(&seed);
+/// Function to build and sign a transaction with an indexation payload, without posting it
+pub async fn build_transaction(
+    ledger: &dyn LedgerBackend,
+    indexation_tag: &str,
+    data: &str,
+    inputs: Option<Vec<OutputId>>,
+    seed: &str,
+    inclusion_position: u64,
+    config: &ChronistConfig,
+) -> Result<Message> {
+    let addresses = ledger.get_addresses(seed, config.rows as usize).await?;
 
-    message_builder = message_builder
-        .with_index(indexation_tag)
-        .with_data(data.as_bytes().to_vec());
-    // For first transaction in row we have to get the input manually from the address
+    let mut outputs = Vec::new();
     let row_for_position = crate::inclusion_structure::get_row_for_position(
         inclusion_position,
-        crate::chronist::INCLUSION_STRUCTURE_ROWS,
-        crate::chronist::INCLUSION_STRUCTURE_SECTION_LENGTH,
+        config.rows,
+        config.section_length,
     );
     for row in 0..row_for_position + 1 {
-        message_builder =
-            message_builder.with_output(&addresses[row as usize].clone(), IOTA_AMOUNT)?;
+        outputs.push((addresses[row as usize], config.amount));
     }
+    let mut inputs = inputs.unwrap_or_default();
     // For first tx in row get outputs from the address
     if inclusion_position
         == crate::inclusion_structure::get_row_starting_position(
             row_for_position,
-            crate::chronist::INCLUSION_STRUCTURE_SECTION_LENGTH,
+            config.section_length,
         )
     {
-        let outputs = client
-            .get_address()
-            .outputs(
-                &addresses[row_for_position as usize],
-                OutputsOptions::default(),
-            )
+        let address_outputs = ledger
+            .get_address_outputs(&addresses[row_for_position as usize])
             .await?;
-        if outputs.is_empty() {
+        if address_outputs.is_empty() {
             return Err(crate::error::Error::UtxoInputNotFound);
         }
         let mut output_index = 0;
-        for (index, output) in outputs.iter().enumerate() {
-            let output_data = client.get_output(&output).await?;
-            let (amount, _, _) =
-                ClientMessageBuilder::get_output_amount_and_address(&output_data.output)?;
-            if amount == IOTA_AMOUNT {
-                output_index = index;
+        for (index, output_id) in address_outputs.iter().enumerate() {
+            let output = ledger.get_output(output_id).await?;
+            if let Some((_, amount)) = output_address_and_amount(&output) {
+                if amount == config.amount {
+                    output_index = index;
+                }
             }
         }
-        message_builder = message_builder.with_input(outputs[output_index].clone());
+        inputs.push(address_outputs[output_index]);
     }
 
-    if let Some(inputs) = inputs {
-        for input in inputs {
-            message_builder = message_builder.with_input(UtxoInput::from(input));
-        }
-    }
-    let message = message_builder.finish().await?;
-    Ok(message)
+    ledger
+        .build_transaction(
+            seed,
+            inputs,
+            outputs,
+            Some((indexation_tag, data.as_bytes())),
+        )
+        .await
 }
 
 /// Function to split initial funds
-pub async fn split_funds(client: &Client, rows: u64, seed: &str) -> Result<Message> {
-    let seed = Seed::from_bytes(&hex::decode(seed)?);
-
-    let addresses_from_seed = client
-        .get_addresses(&seed)
-        .with_range(0..rows as usize)
-        .finish()
-        .await?;
+pub async fn split_funds(
+    ledger: &dyn LedgerBackend,
+    config: &ChronistConfig,
+    seed: &str,
+) -> Result<Message> {
+    let addresses_from_seed = ledger.get_addresses(seed, config.rows as usize).await?;
 
-    while client.get_balance(&seed).finish().await? < rows * IOTA_AMOUNT {
-        println!("Send {}i to {}", rows * IOTA_AMOUNT, addresses_from_seed[0]);
+    let mut inputs = Vec::new();
+    loop {
+        let mut balance = 0;
+        inputs.clear();
+        for address in &addresses_from_seed {
+            for output_id in ledger.get_address_outputs(address).await? {
+                let output = ledger.get_output(&output_id).await?;
+                if let Some((_, amount)) = output_address_and_amount(&output) {
+                    balance += amount;
+                    inputs.push(output_id);
+                }
+            }
+        }
+        if balance >= config.required_funds() {
+            break;
+        }
+        println!(
+            "Send {}i to {}",
+            config.required_funds(),
+            addresses_from_seed[0]
+        );
         sleep(std::time::Duration::from_secs(10)).await;
     }
 
-    let mut message_builder = client.message().with_seed(&seed);
-    for i in 0..rows {
-        message_builder =
-            message_builder.with_output(&addresses_from_seed[i as usize], IOTA_AMOUNT)?;
-    }
-    let message = message_builder.with_index("Chronist").finish().await?;
+    let outputs = addresses_from_seed
+        .iter()
+        .map(|address| (*address, config.amount))
+        .collect();
+    let message = ledger
+        .send_transaction(seed, inputs, outputs, Some(("Chronist", "".as_bytes())))
+        .await?;
 
     println!(
         "Split transaction sent: https://explorer.iota.org/devnet/message/{}",
         message.id().0
     );
-    let _ = client
-        .retry_until_included(&message.id().0, None, None)
-        .await?;
+    ledger.wait_until_included(&message.id().0).await?;
     Ok(message)
 }
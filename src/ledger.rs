@@ -0,0 +1,243 @@
+use crate::error::Result;
+use async_trait::async_trait;
+use iota_client::{
+    api::finish_pow,
+    bee_message::prelude::{
+        Address, Ed25519Address, Essence, Message, MessageId, Output, OutputId, TransactionId,
+        TransactionPayload, UtxoInput,
+    },
+    bee_rest_api::types::dtos::LedgerInclusionStateDto,
+    crypto::hashes::{blake2b::Blake2b256, Digest},
+    node::OutputsOptions,
+    Client, Seed,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerInclusionState {
+    Included,
+    NoTransaction,
+    Conflicting,
+}
+
+/// Referencing info of a message
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MessageMetadata {
+    pub referenced_by_milestone_index: Option<u32>,
+    pub ledger_inclusion_state: Option<LedgerInclusionState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MilestoneInfo {
+    pub index: u32,
+    pub timestamp: u64,
+    pub message_id: MessageId,
+}
+
+/// Ledger access the Chronist and the validation need, so they can run against a node or a simulated ledger
+#[async_trait]
+pub trait LedgerBackend: Send + Sync {
+    /// Get a message by its id
+    async fn get_message(&self, message_id: &MessageId) -> Result<Message>;
+
+    /// Get an output, fails if the output is not known (anymore)
+    async fn get_output(&self, output_id: &OutputId) -> Result<Output>;
+
+    /// Get the output ids of the unspent outputs on an address
+    async fn get_address_outputs(&self, address: &Address) -> Result<Vec<OutputId>>;
+
+    /// Get the first `amount` public addresses of a hex encoded seed
+    async fn get_addresses(&self, seed: &str, amount: usize) -> Result<Vec<Address>>;
+
+    /// Build and sign a transaction with the provided inputs and outputs and an optional indexation payload (index,
+    /// data), without posting it. Without inputs they're selected from the outputs on the addresses of the seed, like
+    /// iota_client does it
+    async fn build_transaction(
+        &self,
+        seed: &str,
+        inputs: Vec<OutputId>,
+        outputs: Vec<(Address, u64)>,
+        indexation: Option<(&str, &[u8])>,
+    ) -> Result<Message>;
+
+    /// Post a message, returns the id it got on the node, which differs from its own id if the node did the PoW
+    async fn post_message(&self, message: &Message) -> Result<MessageId>;
+
+    /// Post a message and get it like it got posted
+    async fn post_and_get_message(&self, message: &Message) -> Result<Message> {
+        let message_id = self.post_message(message).await?;
+        if message_id == message.id().0 {
+            return Ok(message.clone());
+        }
+        self.get_message(&message_id).await
+    }
+
+    /// Build and post a transaction, see `build_transaction`
+    async fn send_transaction(
+        &self,
+        seed: &str,
+        inputs: Vec<OutputId>,
+        outputs: Vec<(Address, u64)>,
+        indexation: Option<(&str, &[u8])>,
+    ) -> Result<Message> {
+        let message = self
+            .build_transaction(seed, inputs, outputs, indexation)
+            .await?;
+        self.post_and_get_message(&message).await
+    }
+
+    /// Wait until a message is included in the ledger
+    async fn wait_until_included(&self, message_id: &MessageId) -> Result<()>;
+
+    /// Get the metadata of a message with the milestone that referenced it
+    async fn get_message_metadata(&self, message_id: &MessageId) -> Result<MessageMetadata>;
+
+    /// Get a milestone by its index
+    async fn get_milestone(&self, index: u32) -> Result<MilestoneInfo>;
+
+    /// Get the included message with a transaction
+    async fn get_included_message(&self, transaction_id: &TransactionId) -> Result<Message>;
+
+    /// Check if an output is known
+    async fn is_output_known(&self, output_id: &OutputId) -> bool {
+        self.get_output(output_id).await.is_ok()
+    }
+}
+
+#[async_trait]
+impl LedgerBackend for Client {
+    async fn get_message(&self, message_id: &MessageId) -> Result<Message> {
+        match Client::get_message(self).data(message_id).await {
+            Ok(message) => Ok(message),
+            Err(iota_client::Error::ResponseError(404, _)) => {
+                Err(crate::error::Error::MessageNotFound)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_output(&self, output_id: &OutputId) -> Result<Output> {
+        let output_response = Client::get_output(self, &UtxoInput::from(*output_id)).await?;
+        Ok(Output::try_from(&output_response.output)?)
+    }
+
+    async fn get_address_outputs(&self, address: &Address) -> Result<Vec<OutputId>> {
+        let bech32_address = address.to_bech32(&self.get_bech32_hrp().await?);
+        let outputs = self
+            .get_address()
+            .outputs(&bech32_address, OutputsOptions::default())
+            .await?;
+        Ok(outputs.iter().map(|output| *output.output_id()).collect())
+    }
+
+    async fn get_addresses(&self, seed: &str, amount: usize) -> Result<Vec<Address>> {
+        Ok(
+            Client::get_addresses(self, &Seed::from_bytes(&hex::decode(seed)?))
+                .with_range(0..amount)
+                .get_all_raw()
+                .await?
+                .into_iter()
+                .filter(|a| !a.1)
+                .map(|a| a.0)
+                .collect(),
+        )
+    }
+
+    async fn build_transaction(
+        &self,
+        seed: &str,
+        inputs: Vec<OutputId>,
+        outputs: Vec<(Address, u64)>,
+        indexation: Option<(&str, &[u8])>,
+    ) -> Result<Message> {
+        let seed = Seed::from_bytes(&hex::decode(seed)?);
+        let bech32_hrp = self.get_bech32_hrp().await?;
+
+        let mut message_builder = self.message().with_seed(&seed);
+        if let Some((index, data)) = indexation {
+            message_builder = message_builder.with_index(index).with_data(data.to_vec());
+        }
+        for input in inputs {
+            message_builder = message_builder.with_input(UtxoInput::from(input));
+        }
+        for (address, amount) in outputs {
+            message_builder =
+                message_builder.with_output(&address.to_bech32(&bech32_hrp), amount)?;
+        }
+        // like `ClientMessageBuilder::finish`, but without posting the message
+        let prepared_transaction = message_builder.prepare_transaction().await?;
+        let payload = message_builder
+            .sign_transaction(prepared_transaction, None, None)
+            .await?;
+        Ok(finish_pow(self, Some(payload)).await?)
+    }
+
+    async fn post_message(&self, message: &Message) -> Result<MessageId> {
+        Ok(Client::post_message(self, message).await?)
+    }
+
+    async fn wait_until_included(&self, message_id: &MessageId) -> Result<()> {
+        let _ = self.retry_until_included(message_id, None, None).await?;
+        Ok(())
+    }
+
+    async fn get_message_metadata(&self, message_id: &MessageId) -> Result<MessageMetadata> {
+        let metadata = Client::get_message(self).metadata(message_id).await?;
+        Ok(MessageMetadata {
+            referenced_by_milestone_index: metadata.referenced_by_milestone_index,
+            ledger_inclusion_state: metadata.ledger_inclusion_state.map(|state| match state {
+                LedgerInclusionStateDto::Included => LedgerInclusionState::Included,
+                LedgerInclusionStateDto::NoTransaction => LedgerInclusionState::NoTransaction,
+                LedgerInclusionStateDto::Conflicting => LedgerInclusionState::Conflicting,
+            }),
+        })
+    }
+
+    async fn get_milestone(&self, index: u32) -> Result<MilestoneInfo> {
+        let milestone = Client::get_milestone(self, index).await?;
+        Ok(MilestoneInfo {
+            index: milestone.index,
+            timestamp: milestone.timestamp,
+            message_id: milestone.message_id,
+        })
+    }
+
+    async fn get_included_message(&self, transaction_id: &TransactionId) -> Result<Message> {
+        Ok(Client::get_included_message(self, transaction_id).await?)
+    }
+}
+
+/// Get the address and amount of an output, treasury outputs have no address
+pub(crate) fn output_address_and_amount(output: &Output) -> Option<(Address, u64)> {
+    match output {
+        Output::SignatureLockedSingle(r) => Some((*r.address(), r.amount())),
+        Output::SignatureLockedDustAllowance(r) => Some((*r.address(), r.amount())),
+        Output::Treasury(_) => None,
+    }
+}
+
+/// Get the id of the output on an address in a transaction, the transactions of the Chronist only have signature
+/// locked outputs
+pub fn find_output_id(
+    transaction: &TransactionPayload,
+    address: &Address,
+) -> Result<OutputId> {
+    let Essence::Regular(essence) = transaction.essence();
+    for (index, output) in essence.outputs().iter().enumerate() {
+        match output_address_and_amount(output) {
+            Some((output_address, _)) if output_address == *address => {
+                return Ok(OutputId::new(transaction.id(), index as u16)?);
+            }
+            Some(_) => {}
+            None => return Err(crate::error::Error::UnsupportedOutputType("treasury")),
+        }
+    }
+    Err(crate::error::Error::UtxoOutputNotFound)
+}
+
+/// Get the Ed25519 address of a public key
+pub(crate) fn address_from_public_key(public_key: &[u8; 32]) -> Address {
+    let hash: [u8; 32] = Blake2b256::digest(public_key).into();
+    Address::Ed25519(Ed25519Address::new(hash))
+}
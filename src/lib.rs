@@ -1,8 +1,16 @@
 pub mod chronist;
 pub mod error;
+pub mod events;
 pub mod inclusion_proof;
 pub mod inclusion_structure;
 pub mod iota_api;
+pub mod ledger;
+pub mod merkle_tree;
+pub mod node_pool;
+pub mod pending_queue;
+pub mod sender;
 pub mod server;
+pub mod simulated_ledger;
 pub mod storage;
 pub mod validation;
+pub mod webhook;
@@ -0,0 +1,144 @@
+use crate::{chronist::ContentHash, error::Result};
+use iota_client::{
+    bee_message::MessageId,
+    crypto::hashes::{blake2b::Blake2b256, Digest},
+};
+use serde::{Deserialize, Serialize};
+
+// Prefixes so a leaf can't be used as a node and the other way around
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+// Prefixes of the leaf data, so a message id can't be used as content hash
+const MESSAGE_ID_LEAF: u8 = 0;
+const CONTENT_HASH_LEAF: u8 = 1;
+
+/// Hash of a leaf or node in the Merkle tree
+pub type MerkleHash = [u8; 32];
+
+/// Root of a Merkle tree over the message ids and hashes of a transaction
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MerkleRoot {
+    /// Hex encoded root hash
+    #[serde(with = "crate::chronist::hex_digest")]
+    pub root: MerkleHash,
+    /// Amount of leaves in the tree
+    pub leaves: u64,
+}
+
+/// Side of a sibling in the audit path
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MerkleSide {
+    Left,
+    Right,
+}
+
+/// Sibling of a node on the path from a leaf to the root
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MerkleNode {
+    pub side: MerkleSide,
+    #[serde(with = "crate::chronist::hex_digest")]
+    pub hash: MerkleHash,
+}
+
+/// Audit path from a leaf to the Merkle root in the inclusion data of the first transaction
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct MerkleProof {
+    pub path: Vec<MerkleNode>,
+}
+
+impl MerkleProof {
+    /// Calculate the root from a leaf and the audit path
+    pub fn root(&self, leaf: MerkleHash) -> MerkleHash {
+        self.path.iter().fold(leaf, |hash, node| match node.side {
+            MerkleSide::Left => hash_node(&node.hash, &hash),
+            MerkleSide::Right => hash_node(&hash, &node.hash),
+        })
+    }
+}
+
+/// Merkle tree with all levels, the first level are the leaves and the last one the root.
+/// A node without sibling is moved up to the next level unchanged.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MerkleTree {
+    levels: Vec<Vec<MerkleHash>>,
+}
+
+impl MerkleTree {
+    /// Build the tree from leaf hashes, see [message_id_leaf] and [content_hash_leaf]
+    pub fn new(leaves: Vec<MerkleHash>) -> Result<Self> {
+        if leaves.is_empty() {
+            return Err(crate::error::Error::InvalidParameter(
+                "Merkle tree needs at least one leaf".to_string(),
+            ));
+        }
+        let mut levels = vec![leaves];
+        while levels[levels.len() - 1].len() > 1 {
+            let level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|nodes| match nodes {
+                    [left, right] => hash_node(left, right),
+                    [node] => *node,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level);
+        }
+        Ok(Self { levels })
+    }
+
+    pub fn root(&self) -> MerkleRoot {
+        MerkleRoot {
+            root: self.levels[self.levels.len() - 1][0],
+            leaves: self.levels[0].len() as u64,
+        }
+    }
+
+    /// Get the audit path for a leaf, None if the leaf isn't part of the tree
+    pub fn proof(&self, leaf: &MerkleHash) -> Option<MerkleProof> {
+        let mut index = self.levels[0].iter().position(|hash| hash == leaf)?;
+        let mut path = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = match index % 2 {
+                0 => level.get(index + 1).map(|hash| MerkleNode {
+                    side: MerkleSide::Right,
+                    hash: *hash,
+                }),
+                _ => Some(MerkleNode {
+                    side: MerkleSide::Left,
+                    hash: level[index - 1],
+                }),
+            };
+            path.extend(sibling);
+            index /= 2;
+        }
+        Some(MerkleProof { path })
+    }
+}
+
+/// Leaf hash of a message id
+pub fn message_id_leaf(message_id: &MessageId) -> MerkleHash {
+    hash_leaf(MESSAGE_ID_LEAF, message_id.as_ref())
+}
+
+/// Leaf hash of a content hash, the algorithm is part of it so the same digest of another algorithm has another leaf
+pub fn content_hash_leaf(content_hash: &ContentHash) -> MerkleHash {
+    let mut data = vec![content_hash.algorithm.tag()];
+    data.extend_from_slice(&content_hash.digest);
+    hash_leaf(CONTENT_HASH_LEAF, &data)
+}
+
+fn hash_leaf(kind: u8, data: &[u8]) -> MerkleHash {
+    let mut hasher = Blake2b256::new();
+    hasher.update([LEAF_PREFIX, kind]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+    let mut hasher = Blake2b256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
@@ -0,0 +1,187 @@
+use crate::{
+    error::{Error, Result},
+    ledger::{LedgerBackend, MessageMetadata, MilestoneInfo},
+};
+use async_trait::async_trait;
+use iota_client::{
+    bee_message::prelude::{Address, Message, MessageId, Output, OutputId, TransactionId},
+    Client,
+};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// Time a node has to answer if an output is known before it counts as not knowing it
+const DEFAULT_QUORUM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Ledger backend over multiple nodes. Requests fail over to the next node if a node returns an error and the
+/// liveness of an output can require the agreement of a quorum of nodes, so a single malicious or lagging node can't
+/// fake the result of a validation.
+pub struct NodePool {
+    nodes: Vec<Arc<dyn LedgerBackend>>,
+    // Node that answered the last request, requests start with it
+    current: AtomicUsize,
+    // Nodes that need to know an output, None if one is enough
+    quorum: Option<usize>,
+    // A node that doesn't answer in time can't block the quorum check
+    quorum_timeout: Duration,
+}
+
+impl NodePool {
+    pub fn new(nodes: Vec<Arc<dyn LedgerBackend>>) -> Result<Self> {
+        if nodes.is_empty() {
+            return Err(Error::InvalidParameter(
+                "node pool needs at least one node".to_string(),
+            ));
+        }
+        Ok(Self {
+            nodes,
+            current: AtomicUsize::new(0),
+            quorum: None,
+            quorum_timeout: DEFAULT_QUORUM_TIMEOUT,
+        })
+    }
+
+    /// Node pool with a client for each node url
+    pub async fn from_urls(node_urls: &[&str]) -> Result<Self> {
+        let mut nodes: Vec<Arc<dyn LedgerBackend>> = Vec::with_capacity(node_urls.len());
+        for node_url in node_urls {
+            nodes.push(Arc::new(
+                Client::builder().with_node(node_url)?.finish().await?,
+            ));
+        }
+        Self::new(nodes)
+    }
+
+    /// Require that at least `quorum` nodes know an output before it's considered known
+    pub fn with_quorum(mut self, quorum: usize) -> Result<Self> {
+        if quorum == 0 || quorum > self.nodes.len() {
+            return Err(Error::InvalidParameter(format!(
+                "quorum needs to be between 1 and the amount of nodes ({})",
+                self.nodes.len()
+            )));
+        }
+        self.quorum = Some(quorum);
+        Ok(self)
+    }
+
+    /// Time each node has to answer for the quorum check, a node that doesn't answer in time doesn't know the
+    /// output. Defaults to 30 seconds.
+    pub fn with_quorum_timeout(mut self, timeout: Duration) -> Self {
+        self.quorum_timeout = timeout;
+        self
+    }
+
+    // Send the request to the nodes until one of them answers it, starting with the node that answered the last one
+    async fn with_failover<'a, T, F, Fut>(&'a self, request: F) -> Result<T>
+    where
+        F: Fn(&'a dyn LedgerBackend) -> Fut + Send,
+        Fut: Future<Output = Result<T>> + Send,
+    {
+        let start = self.current.load(Ordering::SeqCst);
+        let mut last_error = None;
+        for offset in 0..self.nodes.len() {
+            let index = (start + offset) % self.nodes.len();
+            match request(self.nodes[index].as_ref()).await {
+                Ok(response) => {
+                    self.current.store(index, Ordering::SeqCst);
+                    return Ok(response);
+                }
+                Err(e) => {
+                    println!("Request to node {} failed: {}", index, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| Error::InvalidParameter("node pool has no nodes".to_string())))
+    }
+}
+
+#[async_trait]
+impl LedgerBackend for NodePool {
+    async fn get_message(&self, message_id: &MessageId) -> Result<Message> {
+        self.with_failover(|node| node.get_message(message_id))
+            .await
+    }
+
+    async fn get_output(&self, output_id: &OutputId) -> Result<Output> {
+        self.with_failover(|node| node.get_output(output_id)).await
+    }
+
+    async fn get_address_outputs(&self, address: &Address) -> Result<Vec<OutputId>> {
+        self.with_failover(|node| node.get_address_outputs(address))
+            .await
+    }
+
+    async fn get_addresses(&self, seed: &str, amount: usize) -> Result<Vec<Address>> {
+        self.with_failover(|node| node.get_addresses(seed, amount))
+            .await
+    }
+
+    async fn build_transaction(
+        &self,
+        seed: &str,
+        inputs: Vec<OutputId>,
+        outputs: Vec<(Address, u64)>,
+        indexation: Option<(&str, &[u8])>,
+    ) -> Result<Message> {
+        self.with_failover(|node| {
+            node.build_transaction(seed, inputs.clone(), outputs.clone(), indexation)
+        })
+        .await
+    }
+
+    /// Messages are only posted to one node, a retry with another node could create a conflicting transaction if
+    /// the first one got posted despite the error. After an error the next message is posted to the next node.
+    async fn post_message(&self, message: &Message) -> Result<MessageId> {
+        let index = self.current.load(Ordering::SeqCst);
+        let result = self.nodes[index].post_message(message).await;
+        if result.is_err() {
+            self.current
+                .store((index + 1) % self.nodes.len(), Ordering::SeqCst);
+        }
+        result
+    }
+
+    async fn wait_until_included(&self, message_id: &MessageId) -> Result<()> {
+        self.with_failover(|node| node.wait_until_included(message_id))
+            .await
+    }
+
+    async fn get_message_metadata(&self, message_id: &MessageId) -> Result<MessageMetadata> {
+        self.with_failover(|node| node.get_message_metadata(message_id))
+            .await
+    }
+
+    async fn get_milestone(&self, index: u32) -> Result<MilestoneInfo> {
+        self.with_failover(|node| node.get_milestone(index)).await
+    }
+
+    async fn get_included_message(&self, transaction_id: &TransactionId) -> Result<Message> {
+        self.with_failover(|node| node.get_included_message(transaction_id))
+            .await
+    }
+
+    async fn is_output_known(&self, output_id: &OutputId) -> bool {
+        match self.quorum {
+            Some(quorum) => {
+                let known = futures::future::join_all(self.nodes.iter().map(|node| {
+                    tokio::time::timeout(self.quorum_timeout, node.is_output_known(output_id))
+                }))
+                .await;
+                known
+                    .into_iter()
+                    .filter(|known| matches!(known, Ok(true)))
+                    .count()
+                    >= quorum
+            }
+            None => self.get_output(output_id).await.is_ok(),
+        }
+    }
+}
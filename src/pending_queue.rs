@@ -0,0 +1,157 @@
+use crate::chronist::ContentHash;
+use iota_client::bee_message::MessageId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// High priority entries are sent before up to this many older normal priority ones, so they get into the next
+/// transaction, but normal priority ones can't be overtaken forever
+pub const HIGH_PRIORITY_LEAD: u64 = 1000;
+
+/// Priority of a message id or hash that waits to be included
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum Priority {
+    Normal,
+    /// For paid or urgent requests
+    High,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
+    }
+}
+
+/// Message id or hash that waits to be included
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum PendingItem {
+    MessageId(MessageId),
+    Hash(ContentHash),
+}
+
+impl From<MessageId> for PendingItem {
+    fn from(message_id: MessageId) -> Self {
+        PendingItem::MessageId(message_id)
+    }
+}
+
+impl From<&MessageId> for PendingItem {
+    fn from(message_id: &MessageId) -> Self {
+        PendingItem::MessageId(*message_id)
+    }
+}
+
+impl From<ContentHash> for PendingItem {
+    fn from(hash: ContentHash) -> Self {
+        PendingItem::Hash(hash)
+    }
+}
+
+impl From<&ContentHash> for PendingItem {
+    fn from(hash: &ContentHash) -> Self {
+        PendingItem::Hash(*hash)
+    }
+}
+
+/// Message ids and hashes that wait to be included, in the order in which they're sent: by submission, with high
+/// priority ones moved ahead of up to `HIGH_PRIORITY_LEAD` normal ones
+#[derive(Debug, Clone, Default)]
+pub struct PendingQueue {
+    // (rank, sequence) -> item, the sequence makes the keys unique and keeps the submission order
+    order: BTreeMap<(u64, u64), PendingItem>,
+    entries: HashMap<PendingItem, PendingEntry>,
+    next_sequence: u64,
+}
+
+/// Place of a message id or hash in the queue, it's stored for each pending one
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PendingEntry {
+    #[serde(flatten)]
+    pub(crate) item: PendingItem,
+    pub(crate) priority: Priority,
+    pub(crate) sequence: u64,
+}
+
+impl PendingEntry {
+    fn key(&self) -> (u64, u64) {
+        // high priority ones rank like normal ones that were submitted `HIGH_PRIORITY_LEAD` earlier
+        let rank = match self.priority {
+            Priority::Normal => self.sequence + HIGH_PRIORITY_LEAD,
+            Priority::High => self.sequence,
+        };
+        (rank, self.sequence)
+    }
+}
+
+impl PendingQueue {
+    /// Add a message id or hash to the queue, returns false if it's already pending
+    pub fn insert(&mut self, item: impl Into<PendingItem>, priority: Priority) -> bool {
+        let item = item.into();
+        if self.entries.contains_key(&item) {
+            return false;
+        }
+        let entry = PendingEntry {
+            item,
+            priority,
+            sequence: self.next_sequence,
+        };
+        self.next_sequence += 1;
+        self.order.insert(entry.key(), item);
+        self.entries.insert(item, entry);
+        true
+    }
+
+    // Add a stored entry, it keeps its place in the queue. Returns false if the item is already pending
+    pub(crate) fn insert_entry(&mut self, entry: PendingEntry) -> bool {
+        if self.entries.contains_key(&entry.item) {
+            return false;
+        }
+        self.next_sequence = self.next_sequence.max(entry.sequence + 1);
+        self.order.insert(entry.key(), entry.item);
+        self.entries.insert(entry.item, entry);
+        true
+    }
+
+    pub(crate) fn entry(&self, item: impl Into<PendingItem>) -> Option<PendingEntry> {
+        self.entries.get(&item.into()).copied()
+    }
+
+    pub fn remove(&mut self, item: impl Into<PendingItem>) -> bool {
+        match self.entries.remove(&item.into()) {
+            Some(entry) => {
+                self.order.remove(&entry.key());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, item: impl Into<PendingItem>) -> bool {
+        self.entries.contains_key(&item.into())
+    }
+
+    pub fn priority(&self, item: impl Into<PendingItem>) -> Option<Priority> {
+        self.entries.get(&item.into()).map(|entry| entry.priority)
+    }
+
+    /// Amount of message ids and hashes that are sent before this one, None if it's not pending
+    pub fn position(&self, item: impl Into<PendingItem>) -> Option<usize> {
+        let entry = self.entries.get(&item.into())?;
+        Some(self.order.range(..entry.key()).count())
+    }
+
+    /// Message ids and hashes in the order in which they're sent
+    pub fn iter(&self) -> impl Iterator<Item = &PendingItem> {
+        self.order.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
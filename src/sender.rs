@@ -0,0 +1,251 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::{oneshot, Notify};
+
+/// State of the background task that sends the transactions
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SenderStatus {
+    Running,
+    /// No new transactions are sent until it's resumed
+    Paused,
+    /// Shut down, it can't be resumed
+    Stopped,
+}
+
+/// Health of the sender
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SenderHealth {
+    pub status: SenderStatus,
+    pub last_transaction_sent: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Failed transactions since the last one that got sent
+    pub consecutive_failures: u32,
+}
+
+/// When the sender sends a transaction with the pending message ids and hashes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchingPolicy {
+    /// Send as soon as this many message ids and hashes are pending
+    pub batch_size: usize,
+    /// Send at the latest this long after the first of them got pending
+    pub max_latency: Duration,
+    /// Wait before the next try after a failed transaction, doubled with each consecutive failure
+    pub error_backoff: Duration,
+    pub max_error_backoff: Duration,
+}
+
+impl Default for BatchingPolicy {
+    fn default() -> Self {
+        Self {
+            batch_size: 100,
+            max_latency: Duration::from_secs(10),
+            error_backoff: Duration::from_secs(10),
+            max_error_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+impl BatchingPolicy {
+    /// Wait time after `failures` consecutive failed transactions
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.error_backoff
+            .checked_mul(factor)
+            .map_or(self.max_error_backoff, |backoff| {
+                backoff.min(self.max_error_backoff)
+            })
+    }
+}
+
+/// Control of the background task that sends the transactions of a Chronist, see `Chronist::sender`
+#[derive(Clone)]
+pub struct SenderHandle {
+    health: Arc<Mutex<SenderHealth>>,
+    // wakes the sender when the status changed
+    notify: Arc<Notify>,
+    // wakes the sender when message ids or hashes got pending
+    pending: Arc<Notify>,
+    policy: Arc<Mutex<BatchingPolicy>>,
+    // receives when the sender finished after it got stopped
+    finished: Arc<Mutex<Option<oneshot::Receiver<()>>>>,
+}
+
+impl SenderHandle {
+    pub(crate) fn new() -> Self {
+        Self {
+            health: Arc::new(Mutex::new(SenderHealth {
+                status: SenderStatus::Running,
+                last_transaction_sent: None,
+                last_error: None,
+                consecutive_failures: 0,
+            })),
+            notify: Arc::new(Notify::new()),
+            pending: Arc::new(Notify::new()),
+            policy: Arc::new(Mutex::new(BatchingPolicy::default())),
+            finished: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Stop sending new transactions, a transaction that is currently sent is still finished
+    pub fn pause(&self) {
+        self.set_status(SenderStatus::Paused);
+    }
+
+    pub fn resume(&self) {
+        self.set_status(SenderStatus::Running);
+    }
+
+    /// Stop the sender and wait until the current transaction got sent and stored
+    pub async fn shutdown(&self) {
+        self.set_status(SenderStatus::Stopped);
+        let finished = self.lock_finished().take();
+        if let Some(finished) = finished {
+            if finished.await.is_err() {
+                println!("Sender stopped without finishing");
+            }
+        }
+    }
+
+    pub fn status(&self) -> SenderStatus {
+        self.lock_health().status
+    }
+
+    pub fn health(&self) -> SenderHealth {
+        self.lock_health().clone()
+    }
+
+    pub fn batching_policy(&self) -> BatchingPolicy {
+        *self.policy.lock().expect("Batching policy lock poisoned")
+    }
+
+    pub fn set_batching_policy(&self, policy: BatchingPolicy) {
+        *self.policy.lock().expect("Batching policy lock poisoned") = policy;
+        // the sender could wait for a batch that is now big enough
+        self.pending.notify_one();
+    }
+
+    /// A message id or hash got pending, `pending` is the amount of them. Wakes the sender if the queue was empty
+    /// before, so the max latency starts, or if the batch is full.
+    pub(crate) fn pending_added(&self, pending: usize) {
+        if pending == 1 || pending >= self.batching_policy().batch_size {
+            self.pending.notify_one();
+        }
+    }
+
+    /// Channel to signal that the sender finished, `shutdown` waits for it
+    pub(crate) fn finished_channel(&self) -> oneshot::Sender<()> {
+        let (sender, receiver) = oneshot::channel();
+        *self.lock_finished() = Some(receiver);
+        sender
+    }
+
+    pub(crate) fn record_transaction(&self) {
+        let mut health = self.lock_health();
+        health.last_transaction_sent = Some(Utc::now());
+        health.consecutive_failures = 0;
+    }
+
+    pub(crate) fn record_failure(&self, error: &crate::error::Error) {
+        let mut health = self.lock_health();
+        health.last_error = Some(error.to_string());
+        health.consecutive_failures += 1;
+    }
+
+    /// The sender task ended, with an error if it crashed
+    pub(crate) fn record_stop(&self, error: Option<String>) {
+        self.set_status(SenderStatus::Stopped);
+        if let Some(error) = error {
+            self.lock_health().last_error = Some(error);
+        }
+    }
+
+    /// Wait until the sender isn't paused, returns false if it got stopped
+    pub(crate) async fn wait_while_paused(&self) -> bool {
+        loop {
+            // created before the status is read, so a status change in between isn't missed
+            let changed = self.notify.notified();
+            match self.status() {
+                SenderStatus::Running => return true,
+                SenderStatus::Stopped => return false,
+                SenderStatus::Paused => changed.await,
+            }
+        }
+    }
+
+    /// Wait until message ids or hashes got pending, the status changed or the timeout passed, returns false if the
+    /// sender got stopped
+    pub(crate) async fn wait_for_pending(&self, timeout: Option<Duration>) -> bool {
+        let changed = self.notify.notified();
+        if self.status() == SenderStatus::Stopped {
+            return false;
+        }
+        match timeout {
+            Some(timeout) => tokio::select! {
+                _ = tokio::time::sleep(timeout) => {}
+                _ = self.pending.notified() => {}
+                _ = changed => {}
+            },
+            None => tokio::select! {
+                _ = self.pending.notified() => {}
+                _ = changed => {}
+            },
+        }
+        self.status() != SenderStatus::Stopped
+    }
+
+    /// Sleep for the duration, returns early with false if the sender got stopped
+    pub(crate) async fn sleep(&self, duration: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + duration;
+        loop {
+            let changed = self.notify.notified();
+            if self.status() == SenderStatus::Stopped {
+                return false;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => return true,
+                // pausing or resuming doesn't shorten the sleep
+                _ = changed => {}
+            }
+        }
+    }
+
+    fn set_status(&self, status: SenderStatus) {
+        let mut health = self.lock_health();
+        // a stopped sender can't be restarted
+        if health.status != SenderStatus::Stopped {
+            health.status = status;
+        }
+        drop(health);
+        // the sender loop and the webhook deliveries can both wait
+        self.notify.notify_waiters();
+    }
+
+    fn lock_health(&self) -> std::sync::MutexGuard<'_, SenderHealth> {
+        self.health.lock().expect("Sender health lock poisoned")
+    }
+
+    fn lock_finished(&self) -> std::sync::MutexGuard<'_, Option<oneshot::Receiver<()>>> {
+        self.finished.lock().expect("Sender finished lock poisoned")
+    }
+}
+
+/// Stops the sender when the Chronist that owns it is dropped, the background tasks only share the handle
+pub(crate) struct SenderGuard(SenderHandle);
+
+impl SenderGuard {
+    pub(crate) fn new(sender: SenderHandle) -> Self {
+        Self(sender)
+    }
+}
+
+impl Drop for SenderGuard {
+    fn drop(&mut self) {
+        self.0.set_status(SenderStatus::Stopped);
+    }
+}
@@ -1,40 +1,106 @@
 use crate::{
-    chronist::{Chronist, UtxoData, INCLUSION_INDEX, TRANSACTION_MESSAGE_KEY},
+    chronist::{Chronist, ContentHash, HashAlgorithm, SaveMessageStatus, UtxoData},
     error::Result,
+    storage::{StorageExt, StorageKey},
 };
-use iota_client::{
-    bee_message::{Message, MessageId},
-    bee_rest_api::types::dtos::MessageDto,
-};
+use futures::{Future, StreamExt};
+use iota_client::{bee_message::Message, bee_rest_api::types::dtos::MessageDto};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{broadcast, watch, RwLock};
 use warp::{path, Filter, Rejection, Reply};
 
-/// Start the API server
+const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+const DEFAULT_LIST_LIMIT: usize = 1000;
+const MAX_LIST_LIMIT: usize = 10_000;
+/// Max amount of message ids in the body of a batch request
+const MAX_BATCH_SIZE: usize = 1000;
+// A hex encoded message id with quotes and separator has 67 bytes
+const MAX_BATCH_BODY_SIZE: u64 = 128 * 1024;
+// Documents are hex encoded in the body of POST /proof/create-hash, so they can have up to 4 MiB
+const MAX_HASH_BODY_SIZE: u64 = 8 * 1024 * 1024 + 1024;
+
+/// Start the API server, on SIGTERM or Ctrl-C it stops accepting requests and shuts the sender down after the
+/// current transaction
 pub async fn start(chronist: Chronist, port: u16) -> Result<()> {
+    start_with_shutdown(chronist, port, shutdown_signal()).await
+}
+
+/// Start the API server and shut it down like [`start`] once `shutdown` completes
+pub async fn start_with_shutdown(
+    chronist: Chronist,
+    port: u16,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    let sender = chronist.sender();
+    // Open event streams end when the shutdown starts, otherwise the graceful shutdown would wait for them forever
+    let (stopping_tx, stopping) = watch::channel(false);
     let chronist = Arc::new(RwLock::new(chronist));
     // GET /
     let api_endpoints = warp::any().map(|| {
-        "Available endpoints:\nGET /proof/create/:messageId\nGET /proof/get/:messageId\nPOST /proof/is-valid/\nGET /messages/list\nGET /messages/:messageId\nGET /messages/position/:index"
+        "Available endpoints:\nGET /proof/create/:messageId\nPOST /proof/create-batch\nPOST /proof/create-hash\nGET /proof/get/:messageId\nGET /proof/get-hash/:hash?algorithm=\nPOST /proof/get-batch\nPOST /proof/is-valid/\nPOST /proof/is-valid-hash\nPOST /proof/refresh\nGET /messages/list?cursor=&limit=\nGET /messages/queue/:messageId\nGET /messages/:messageId\nGET /messages/position/:index\nGET /events\nGET /health"
     });
 
-    // GET /proof/create/:messageId
+    // GET /proof/create/:messageId?callback=url
+    // With a callback url the proof gets posted to it once the message is included
     let create = warp::path("proof")
         .and(path("create"))
         .and(warp::path::param())
+        .and(warp::query::<CreationQuery>())
+        .and_then({
+            let chronist_ = chronist.clone();
+            move |m, q| proof_creation_handler(m, q, chronist_.clone())
+        });
+
+    // POST /proof/create-batch
+    // Body is a list of message ids, returns the status for each of them, also with an optional ?callback=url
+    let create_batch = warp::post()
+        .and(path("proof"))
+        .and(path("create-batch"))
+        .and(warp::query::<CreationQuery>())
+        .and(warp::body::content_length_limit(MAX_BATCH_BODY_SIZE))
+        .and(warp::body::json())
         .and_then({
             let chronist_ = chronist.clone();
-            move |m| proof_creation_handler(m, chronist_.clone())
+            move |q, message_ids: Vec<String>| {
+                proof_creation_batch_handler(message_ids, q, chronist_.clone())
+            }
+        });
+
+    // POST /proof/create-hash
+    // Body has either the hex encoded `data` of a document or an existing hex encoded `hash` with its `algorithm`
+    let create_hash = warp::post()
+        .and(path("proof"))
+        .and(path("create-hash"))
+        .and(warp::body::content_length_limit(MAX_HASH_BODY_SIZE))
+        .and(warp::body::json())
+        .and_then({
+            let chronist_ = chronist.clone();
+            move |request: CreateHashRequest| {
+                proof_creation_hash_handler(request, chronist_.clone())
+            }
         });
 
-    // GET /proof/get/:messageId
+    // GET /proof/get-hash/:hash?algorithm=sha256&milestone=true
+    let get_hash = warp::path("proof")
+        .and(path("get-hash"))
+        .and(warp::path::param())
+        .and(warp::query::<HashProofQuery>())
+        .and_then({
+            let chronist_ = chronist.clone();
+            move |h, q| proof_get_hash_handler(h, q, chronist_.clone())
+        });
+
+    // GET /proof/get/:messageId?milestone=true
+    // With "Accept: application/octet-stream" the proof is returned in the binary format
     let get = warp::path("proof")
         .and(path("get"))
         .and(warp::path::param())
+        .and(warp::query::<ProofQuery>())
+        .and(warp::header::optional::<String>("accept"))
         .and_then({
             let chronist_ = chronist.clone();
-            move |m| proof_get_handler(m, chronist_.clone())
+            move |m, q, a| proof_get_handler(m, q, a, chronist_.clone())
         });
 
     // POST /proof/is-valid/
@@ -49,12 +115,69 @@ pub async fn start(chronist: Chronist, port: u16) -> Result<()> {
             }
         });
 
-    // GET /messages/list
-    let messages = warp::path("messages").and(path("list")).and_then({
+    // POST /proof/get-batch
+    // Body is a list of message ids, returns a proof or an error for each of them
+    let get_batch = warp::post()
+        .and(path("proof"))
+        .and(path("get-batch"))
+        .and(warp::body::content_length_limit(MAX_BATCH_BODY_SIZE))
+        .and(warp::body::json())
+        .and_then({
+            let chronist_ = chronist.clone();
+            move |message_ids: Vec<String>| proof_get_batch_handler(message_ids, chronist_.clone())
+        });
+
+    // POST /proof/is-valid-hash
+    let is_valid_hash = warp::post()
+        .and(path("proof"))
+        .and(path("is-valid-hash"))
+        .and(warp::body::json())
+        .and_then({
+            let chronist_ = chronist.clone();
+            move |hash_proof: crate::inclusion_proof::HashInclusionProof| {
+                proof_is_valid_hash_handler(hash_proof, chronist_.clone())
+            }
+        });
+
+    // POST /proof/refresh
+    let refresh = warp::post()
+        .and(path("proof"))
+        .and(path("refresh"))
+        .and(warp::body::json())
+        .and_then({
+            let chronist_ = chronist.clone();
+            move |inclusion_proof: crate::inclusion_proof::InclusionProof| {
+                proof_refresh_handler(inclusion_proof, chronist_.clone())
+            }
+        });
+
+    // GET /events
+    // Server-sent events of the Chronist activity
+    let events = warp::path("events").and(warp::path::end()).and_then({
         let chronist_ = chronist.clone();
-        move || list_messages_handler(chronist_.clone())
+        move || events_handler(chronist_.clone(), stopping.clone())
     });
 
+    // GET /messages/list?cursor=messageId&limit=1000
+    // Ids of the included messages in pages, the next page starts after the `nextCursor` of the previous one
+    let messages = warp::path("messages")
+        .and(path("list"))
+        .and(warp::query::<ListQuery>())
+        .and_then({
+            let chronist_ = chronist.clone();
+            move |q| list_messages_handler(q, chronist_.clone())
+        });
+
+    // GET /messages/queue/:messageId
+    // Position of a pending message id in the queue
+    let message_queue = warp::path("messages")
+        .and(path("queue"))
+        .and(warp::path::param())
+        .and_then({
+            let chronist_ = chronist.clone();
+            move |m| messages_queue_handler(m, chronist_.clone())
+        });
+
     // GET /messages/:messageId
     let message = warp::path("messages").and(warp::path::param()).and_then({
         let chronist_ = chronist.clone();
@@ -70,34 +193,197 @@ pub async fn start(chronist: Chronist, port: u16) -> Result<()> {
             move |p| messages_position_get_handler(p, chronist_.clone())
         });
 
-    let routes = is_valid.or(create
-        .or(get)
-        .or(messages)
-        .or(message)
-        .or(message_position)
-        .or(api_endpoints));
-    warp::serve(routes).run(([127, 0, 0, 1], port)).await;
+    // GET /health
+    // Status of the sender, when the last transaction got sent and the last error
+    let health = warp::path("health").and(warp::path::end()).map({
+        let sender = sender.clone();
+        move || warp::reply::json(&sender.health())
+    });
+
+    let routes = is_valid
+        .or(is_valid_hash)
+        .or(refresh)
+        .or(get_batch)
+        .or(create_batch)
+        .or(create_hash)
+        .or(get_hash)
+        .or(create
+            .or(get)
+            .or(messages)
+            .or(message_queue)
+            .or(message)
+            .or(message_position)
+            .or(events)
+            .or(health)
+            .or(api_endpoints));
+    let shutdown = async move {
+        shutdown.await;
+        let _ = stopping_tx.send(true);
+    };
+    let (_, server) =
+        warp::serve(routes).bind_with_graceful_shutdown(([127, 0, 0, 1], port), shutdown);
+    server.await;
+    println!("Shutting down the sender");
+    sender.shutdown().await;
     Ok(())
 }
 
+// Completes on SIGTERM or Ctrl-C
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(terminate) => terminate,
+                Err(e) => {
+                    println!("Couldn't listen for SIGTERM: {}", e);
+                    let _ = tokio::signal::ctrl_c().await;
+                    return;
+                }
+            };
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
 pub async fn proof_creation_handler(
     message_id: String,
+    query: CreationQuery,
     chronist: Arc<RwLock<Chronist>>,
 ) -> std::result::Result<impl Reply, Rejection> {
     let chronist = chronist.read().await;
     chronist.save_message(&message_id).await?;
+    if let Some(callback) = query.callback {
+        chronist.register_callback(&message_id, &callback).await?;
+    }
     println!("proof_creation_handler message_id: {}", message_id);
     Ok(warp::reply::json(&MessageIdResponse { message_id }))
 }
 
+pub async fn proof_creation_batch_handler(
+    message_ids: Vec<String>,
+    query: CreationQuery,
+    chronist: Arc<RwLock<Chronist>>,
+) -> std::result::Result<impl Reply, Rejection> {
+    check_batch_size(&message_ids)?;
+    let chronist = chronist.read().await;
+    let mut statuses: HashMap<String, CreationResult> = HashMap::new();
+    for (message_id, status) in chronist.save_messages(&message_ids).await {
+        let status = match (status, &query.callback) {
+            (Ok(status), Some(callback)) if status != SaveMessageStatus::NotFoundOnNode => chronist
+                .register_callback(&message_id, callback)
+                .await
+                .map(|_| status),
+            (status, _) => status,
+        };
+        let result = match status {
+            Ok(status) => CreationResult {
+                status: Some(status),
+                error: None,
+            },
+            Err(e) => CreationResult {
+                status: None,
+                error: Some(e.to_string()),
+            },
+        };
+        statuses.insert(message_id, result);
+    }
+    Ok(warp::reply::json(&statuses))
+}
+
+pub async fn proof_creation_hash_handler(
+    request: CreateHashRequest,
+    chronist: Arc<RwLock<Chronist>>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let hash = match request {
+        CreateHashRequest {
+            data: Some(data), ..
+        } => ContentHash::from_data(&hex::decode(data).map_err(crate::error::Error::from)?),
+        CreateHashRequest {
+            hash: Some(hash),
+            algorithm,
+            ..
+        } => ContentHash::from_hex(algorithm.unwrap_or(HashAlgorithm::Sha256), &hash)?,
+        _ => {
+            return Err(reject::custom(CustomRejection::BadRequest(
+                "data or hash required".to_string(),
+            )))
+        }
+    };
+    let chronist = chronist.read().await;
+    let status = chronist.save_hash(hash).await?;
+    Ok(warp::reply::json(&CreateHashResponse { hash, status }))
+}
+
+pub async fn proof_get_hash_handler(
+    hash: String,
+    query: HashProofQuery,
+    chronist: Arc<RwLock<Chronist>>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let chronist = chronist.read().await;
+    let proof = chronist
+        .get_hash_proof(&hash, query.algorithm, query.milestone.unwrap_or(false))
+        .await?;
+    Ok(warp::reply::json(&proof))
+}
+
 pub async fn proof_get_handler(
     message_id: String,
+    query: ProofQuery,
+    accept: Option<String>,
     chronist: Arc<RwLock<Chronist>>,
-) -> std::result::Result<impl Reply, Rejection> {
+) -> std::result::Result<warp::reply::Response, Rejection> {
     println!("proof_get_handler message_id: {}", message_id);
     let chronist = chronist.read().await;
-    let proof = chronist.get_message_proof(&message_id).await?;
-    Ok(warp::reply::json(&proof))
+    let proof = if query.milestone.unwrap_or(false) {
+        chronist
+            .get_message_proof_with_milestone(&message_id)
+            .await?
+    } else {
+        chronist.get_message_proof(&message_id).await?
+    };
+    match accept {
+        Some(accept) if accept.contains(BINARY_CONTENT_TYPE) => Ok(warp::reply::with_header(
+            proof.to_bytes(&chronist.config())?,
+            "content-type",
+            BINARY_CONTENT_TYPE,
+        )
+        .into_response()),
+        _ => Ok(warp::reply::json(&proof).into_response()),
+    }
+}
+
+pub async fn proof_get_batch_handler(
+    message_ids: Vec<String>,
+    chronist: Arc<RwLock<Chronist>>,
+) -> std::result::Result<impl Reply, Rejection> {
+    check_batch_size(&message_ids)?;
+    let chronist = chronist.read().await;
+    let proofs: HashMap<String, ProofResult> = chronist
+        .get_message_proofs(&message_ids)
+        .await?
+        .into_iter()
+        .map(|(message_id, proof)| {
+            let result = match proof {
+                Ok(proof) => ProofResult {
+                    proof: Some(proof),
+                    error: None,
+                },
+                Err(e) => ProofResult {
+                    proof: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            (message_id, result)
+        })
+        .collect();
+    Ok(warp::reply::json(&proofs))
 }
 
 pub async fn proof_is_valid_handler(
@@ -105,20 +391,87 @@ pub async fn proof_is_valid_handler(
     chronist: Arc<RwLock<Chronist>>,
 ) -> std::result::Result<impl Reply, Rejection> {
     let chronist = chronist.read().await;
-    let is_valid = inclusion_proof.is_valid(&chronist.iota_client).await?;
-    println!("Requested proof is valid: {}", is_valid);
+    let verdict = inclusion_proof.verdict(chronist.ledger.as_ref()).await;
+    println!("Requested proof is valid: {}", verdict.valid);
+
+    Ok(warp::reply::json(&verdict))
+}
+
+pub async fn proof_is_valid_hash_handler(
+    hash_proof: crate::inclusion_proof::HashInclusionProof,
+    chronist: Arc<RwLock<Chronist>>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let chronist = chronist.read().await;
+    let verdict = hash_proof.verdict(chronist.ledger.as_ref()).await;
+    Ok(warp::reply::json(&verdict))
+}
+
+pub async fn proof_refresh_handler(
+    inclusion_proof: crate::inclusion_proof::InclusionProof,
+    chronist: Arc<RwLock<Chronist>>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let chronist = chronist.read().await;
+    let proof = chronist.refresh_proof(inclusion_proof).await?;
+    Ok(warp::reply::json(&proof))
+}
 
-    Ok(warp::reply::json(&is_valid))
+pub async fn events_handler(
+    chronist: Arc<RwLock<Chronist>>,
+    mut stopping: watch::Receiver<bool>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let receiver = chronist.read().await.subscribe_events();
+    let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let sse_event = warp::sse::Event::default()
+                        .event(event.name())
+                        .json_data(&event);
+                    return Some((sse_event, receiver));
+                }
+                // skip events the subscriber missed because it was too slow
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+    .take_until(async move {
+        while !*stopping.borrow() {
+            if stopping.changed().await.is_err() {
+                return;
+            }
+        }
+    });
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(stream)))
 }
 
 pub async fn list_messages_handler(
+    query: ListQuery,
+    chronist: Arc<RwLock<Chronist>>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let limit = match query.limit {
+        Some(0) => {
+            return Err(reject::custom(CustomRejection::BadRequest(
+                "limit must be at least 1".to_string(),
+            )))
+        }
+        Some(limit) => limit.min(MAX_LIST_LIMIT),
+        None => DEFAULT_LIST_LIMIT,
+    };
+    let chronist = chronist.read().await;
+    let page = chronist
+        .get_message_ids(query.cursor.as_deref(), limit)
+        .await?;
+    Ok(warp::reply::json(&page))
+}
+
+pub async fn messages_queue_handler(
+    message_id: String,
     chronist: Arc<RwLock<Chronist>>,
 ) -> std::result::Result<impl Reply, Rejection> {
     let chronist = chronist.read().await;
-    let message_ids = chronist.message_ids.read().await;
-    Ok(warp::reply::json(
-        &message_ids.iter().collect::<Vec<&MessageId>>(),
-    ))
+    let position = chronist.get_queue_position(&message_id).await?;
+    Ok(warp::reply::json(&position))
 }
 
 pub async fn messages_get_handler(
@@ -136,6 +489,16 @@ pub async fn messages_get_handler(
 }
 use warp::reject;
 
+fn check_batch_size(message_ids: &[String]) -> std::result::Result<(), Rejection> {
+    if message_ids.len() > MAX_BATCH_SIZE {
+        return Err(reject::custom(CustomRejection::BadRequest(format!(
+            "at most {} message ids per request",
+            MAX_BATCH_SIZE
+        ))));
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub(crate) enum CustomRejection {
     // Forbidden,
@@ -151,40 +514,12 @@ pub async fn messages_position_get_handler(
     chronist: Arc<RwLock<Chronist>>,
 ) -> std::result::Result<impl Reply, Rejection> {
     let chronist = chronist.read().await;
-    let position_data: UtxoData = match serde_json::from_str(
-        &chronist
-            .db
-            .lock()
-            .await
-            .get(&format!("{}{}", INCLUSION_INDEX, position))
-            .await?,
-    ) {
-        Ok(id) => id,
-        Err(_) => {
-            return Err(reject::custom(CustomRejection::BadRequest(
-                "Couldn't decode for message".to_string(),
-            )))
-        }
-    };
-    let message: Message = match serde_json::from_str(
-        &chronist
-            .db
-            .lock()
-            .await
-            .get(&format!(
-                "{}{}",
-                TRANSACTION_MESSAGE_KEY,
-                position_data.message_id.to_string()
-            ))
-            .await?,
-    ) {
-        Ok(message) => message,
-        Err(_) => {
-            return Err(reject::custom(CustomRejection::BadRequest(
-                "Couldn't decode for message".to_string(),
-            )))
-        }
-    };
+    let database = chronist.db.lock().await;
+    let position_data: UtxoData = database.get(&StorageKey::Position(position)).await?;
+    let message: Message = database
+        .get(&StorageKey::TransactionMessage(position_data.message_id))
+        .await?;
+    drop(database);
 
     let response = MessageResponse {
         data: MessageDto::from(&message),
@@ -193,6 +528,37 @@ pub async fn messages_position_get_handler(
     Ok(warp::reply::json(&response))
 }
 
+/// Query parameters of GET /messages/list
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListQuery {
+    /// Message id after which the page starts, the `nextCursor` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Query parameters of GET /proof/create/:messageId and POST /proof/create-batch
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreationQuery {
+    /// Url to which the proof gets posted once the message is included and the transaction confirmed
+    pub callback: Option<String>,
+}
+
+/// Query parameters of GET /proof/get/:messageId
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProofQuery {
+    /// Add the milestone that referenced the last transaction to the proof
+    pub milestone: Option<bool>,
+}
+
+/// Query parameters of GET /proof/get-hash/:hash
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HashProofQuery {
+    /// Required if the digest is stored for more than one algorithm
+    pub algorithm: Option<HashAlgorithm>,
+    /// Add the milestone that referenced the last transaction to the proof
+    pub milestone: Option<bool>,
+}
+
 /// Response of GET /api/v1/messages?index={INDEX}.
 /// Returns all messages ids that match a given indexation key.
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -201,6 +567,43 @@ pub struct MessageIdResponse {
     pub message_id: String,
 }
 
+/// Body of POST /proof/create-hash
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreateHashRequest {
+    /// Hex encoded document, gets hashed with BLAKE2b-256
+    pub data: Option<String>,
+    /// Hex encoded digest of a document
+    pub hash: Option<String>,
+    /// Algorithm of the digest, default is sha256
+    pub algorithm: Option<HashAlgorithm>,
+}
+
+/// Response of POST /proof/create-hash
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreateHashResponse {
+    pub hash: ContentHash,
+    #[serde(flatten)]
+    pub status: SaveMessageStatus,
+}
+
+/// Result for a single message id of POST /proof/create-batch, either the status or the error
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreationResult {
+    #[serde(flatten)]
+    pub status: Option<SaveMessageStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result for a single message id of POST /proof/get-batch, either the proof or the error
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProofResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof: Option<crate::inclusion_proof::InclusionProof>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 /// Response of GET /api/v1/messages?index={INDEX}.
 /// Returns all messages ids that match a given indexation key.
 #[derive(Clone, Debug, Serialize, Deserialize)]
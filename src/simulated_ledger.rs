@@ -0,0 +1,392 @@
+use crate::{
+    error::Result,
+    ledger::{
+        address_from_public_key, output_address_and_amount, LedgerBackend, LedgerInclusionState,
+        MessageMetadata, MilestoneInfo,
+    },
+};
+use async_trait::async_trait;
+use iota_client::{
+    bee_message::prelude::{
+        Address, Ed25519Signature, Essence, IndexationPayload, Input, Message, MessageBuilder,
+        MessageId, Output, OutputId, Parents, Payload, ReferenceUnlock, RegularEssence,
+        SignatureLockedSingleOutput, SignatureUnlock, TransactionId, TransactionPayload,
+        UnlockBlock, UnlockBlocks, UtxoInput,
+    },
+    crypto::{
+        keys::slip10::{Chain, Curve},
+        signatures::ed25519::SecretKey,
+    },
+    Seed,
+};
+use std::{collections::HashMap, sync::Mutex};
+
+const HD_WALLET_TYPE: u32 = 44;
+const IOTA_COIN_TYPE: u32 = 4218;
+// Amount of addresses that is searched for the keys of the inputs
+const ADDRESS_SEARCH_RANGE: u32 = 100;
+
+/// In memory UTXO ledger to run the Chronist and the validation without a node.
+/// Messages are included instantly by their own milestone and spent outputs are forgotten, like a node would
+/// do after pruning.
+#[derive(Default)]
+pub struct SimulatedLedger {
+    state: Mutex<SimulatedLedgerState>,
+}
+
+/// Failure of posted messages, to test how the Chronist handles node errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostFailure {
+    /// The node rejects the message
+    Rejected,
+    /// The node accepts the message, but the response gets lost
+    ResponseLost,
+}
+
+#[derive(Default)]
+struct SimulatedLedgerState {
+    messages: HashMap<MessageId, Message>,
+    unspent_outputs: HashMap<OutputId, Output>,
+    latest_message_id: Option<MessageId>,
+    metadata: HashMap<MessageId, MessageMetadata>,
+    milestones: Vec<MilestoneInfo>,
+    faucet_transactions: u64,
+    post_failure: Option<PostFailure>,
+}
+
+impl SimulatedLedgerState {
+    fn insert_message(&mut self, message: &Message) {
+        let message_id = message.id().0;
+        // every message is referenced by a new milestone, starting with index 1
+        let milestone_index = self.milestones.len() as u32 + 1;
+        self.milestones.push(MilestoneInfo {
+            index: milestone_index,
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            message_id,
+        });
+        let ledger_inclusion_state = match message.payload() {
+            Some(Payload::Transaction(_)) => LedgerInclusionState::Included,
+            _ => LedgerInclusionState::NoTransaction,
+        };
+        self.metadata.insert(
+            message_id,
+            MessageMetadata {
+                referenced_by_milestone_index: Some(milestone_index),
+                ledger_inclusion_state: Some(ledger_inclusion_state),
+            },
+        );
+        self.messages.insert(message_id, message.clone());
+        self.latest_message_id = Some(message_id);
+    }
+}
+
+impl SimulatedLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an unspent output on an address out of nothing
+    pub fn fund(&self, address: Address, amount: u64) -> Result<OutputId> {
+        let mut state = self.state.lock().expect("Simulated ledger lock poisoned");
+        state.faucet_transactions += 1;
+        // faucet transaction ids can't collide with the hash of a real transaction
+        let mut transaction_id = [0u8; 32];
+        transaction_id[24..].copy_from_slice(&state.faucet_transactions.to_be_bytes());
+        let output_id = OutputId::new(TransactionId::new(transaction_id), 0)?;
+        state.unspent_outputs.insert(
+            output_id,
+            Output::SignatureLockedSingle(SignatureLockedSingleOutput::new(address, amount)?),
+        );
+        Ok(output_id)
+    }
+
+    /// Add a message with an indexation payload, like it would be sent by someone else
+    pub fn add_indexation_message(&self, index: &str, data: &[u8]) -> Result<MessageId> {
+        let payload =
+            Payload::Indexation(Box::new(IndexationPayload::new(index.as_bytes(), data)?));
+        let message = self.build_message(payload)?;
+        self.state
+            .lock()
+            .expect("Simulated ledger lock poisoned")
+            .insert_message(&message);
+        Ok(message.id().0)
+    }
+
+    /// Let all posted messages fail until it's reset with None
+    pub fn set_post_failure(&self, failure: Option<PostFailure>) {
+        self.state
+            .lock()
+            .expect("Simulated ledger lock poisoned")
+            .post_failure = failure;
+    }
+
+    // Message with the latest message as parent
+    fn build_message(&self, payload: Payload) -> Result<Message> {
+        let state = self.state.lock().expect("Simulated ledger lock poisoned");
+        let parent = state
+            .latest_message_id
+            .unwrap_or_else(|| MessageId::new([0u8; 32]));
+        Ok(MessageBuilder::<u64>::new()
+            .with_network_id(0)
+            .with_parents(Parents::new(vec![parent])?)
+            .with_payload(payload)
+            .with_nonce_provider(0, 0f64)
+            .finish()?)
+    }
+}
+
+impl SimulatedLedger {
+    // Select outputs from the addresses of the seed in order, the largest ones of an address first, until they cover
+    // the amount
+    fn select_inputs(&self, seed: &Seed, required_amount: u64) -> Result<Vec<OutputId>> {
+        let state = self.state.lock().expect("Simulated ledger lock poisoned");
+        let mut inputs = Vec::new();
+        let mut input_amount = 0;
+        for address_index in 0..ADDRESS_SEARCH_RANGE {
+            if input_amount >= required_amount {
+                break;
+            }
+            let address = address_from_secret_key(&derive_secret_key(seed, address_index)?);
+            let mut address_outputs: Vec<(OutputId, u64)> = state
+                .unspent_outputs
+                .iter()
+                .filter_map(
+                    |(output_id, output)| match output_address_and_amount(output) {
+                        Some((output_address, amount)) if output_address == address => {
+                            Some((*output_id, amount))
+                        }
+                        _ => None,
+                    },
+                )
+                .collect();
+            address_outputs.sort_by(|(_, l), (_, r)| r.cmp(l));
+            for (output_id, amount) in address_outputs {
+                if input_amount >= required_amount {
+                    break;
+                }
+                inputs.push(output_id);
+                input_amount += amount;
+            }
+        }
+        Ok(inputs)
+    }
+}
+
+/// Get the private key for an address index, derived the same way as iota_client does it
+fn derive_secret_key(seed: &Seed, address_index: u32) -> Result<SecretKey> {
+    let chain = Chain::from_u32_hardened(vec![HD_WALLET_TYPE, IOTA_COIN_TYPE, 0, 0, address_index]);
+    Ok(seed.derive(Curve::Ed25519, &chain)?.secret_key()?)
+}
+
+fn address_from_secret_key(secret_key: &SecretKey) -> Address {
+    address_from_public_key(&secret_key.public_key().to_compressed_bytes())
+}
+
+#[async_trait]
+impl LedgerBackend for SimulatedLedger {
+    async fn get_message(&self, message_id: &MessageId) -> Result<Message> {
+        let state = self.state.lock().expect("Simulated ledger lock poisoned");
+        state
+            .messages
+            .get(message_id)
+            .cloned()
+            .ok_or(crate::error::Error::MessageNotFound)
+    }
+
+    async fn get_included_message(&self, transaction_id: &TransactionId) -> Result<Message> {
+        let state = self.state.lock().expect("Simulated ledger lock poisoned");
+        state
+            .messages
+            .values()
+            .find(|message| match message.payload() {
+                Some(Payload::Transaction(tx)) => tx.id() == *transaction_id,
+                _ => false,
+            })
+            .cloned()
+            .ok_or(crate::error::Error::MessageNotFound)
+    }
+
+    async fn get_output(&self, output_id: &OutputId) -> Result<Output> {
+        let state = self.state.lock().expect("Simulated ledger lock poisoned");
+        state
+            .unspent_outputs
+            .get(output_id)
+            .cloned()
+            .ok_or(crate::error::Error::OutputNotFound)
+    }
+
+    async fn get_address_outputs(&self, address: &Address) -> Result<Vec<OutputId>> {
+        let state = self.state.lock().expect("Simulated ledger lock poisoned");
+        Ok(state
+            .unspent_outputs
+            .iter()
+            .filter(|(_, output)| {
+                matches!(output_address_and_amount(output), Some((output_address, _)) if output_address == *address)
+            })
+            .map(|(output_id, _)| *output_id)
+            .collect())
+    }
+
+    async fn get_addresses(&self, seed: &str, amount: usize) -> Result<Vec<Address>> {
+        let seed = Seed::from_bytes(&hex::decode(seed)?);
+        (0..amount as u32)
+            .map(|index| Ok(address_from_secret_key(&derive_secret_key(&seed, index)?)))
+            .collect()
+    }
+
+    async fn build_transaction(
+        &self,
+        seed: &str,
+        mut inputs: Vec<OutputId>,
+        mut outputs: Vec<(Address, u64)>,
+        indexation: Option<(&str, &[u8])>,
+    ) -> Result<Message> {
+        let seed = Seed::from_bytes(&hex::decode(seed)?);
+        if inputs.is_empty() {
+            inputs = self.select_inputs(&seed, outputs.iter().map(|(_, amount)| amount).sum())?;
+        }
+        // same order as the packed bytes, which is required for the essence
+        inputs.sort_by_key(|output_id| output_id.to_string());
+        inputs.dedup();
+
+        // get the addresses and the amount of the inputs
+        let mut input_addresses = Vec::new();
+        let mut input_amount = 0;
+        {
+            let state = self.state.lock().expect("Simulated ledger lock poisoned");
+            for input in &inputs {
+                let (address, amount) = state
+                    .unspent_outputs
+                    .get(input)
+                    .and_then(output_address_and_amount)
+                    .ok_or(crate::error::Error::UtxoInputNotFound)?;
+                input_addresses.push(address);
+                input_amount += amount;
+            }
+        }
+        let output_amount: u64 = outputs.iter().map(|(_, amount)| amount).sum();
+        if input_amount < output_amount || inputs.is_empty() {
+            return Err(crate::error::Error::NotEnoughFunds);
+        }
+        // send the remainder back to the address of the first input
+        if input_amount > output_amount {
+            outputs.push((input_addresses[0], input_amount - output_amount));
+        }
+        outputs.sort_by_key(|(address, amount)| {
+            let Address::Ed25519(address) = address;
+            (address.as_ref().to_vec(), *amount)
+        });
+
+        let mut essence_builder = RegularEssence::builder();
+        for input in &inputs {
+            essence_builder = essence_builder.add_input(Input::Utxo(UtxoInput::from(*input)));
+        }
+        for (address, amount) in outputs {
+            essence_builder = essence_builder.add_output(Output::SignatureLockedSingle(
+                SignatureLockedSingleOutput::new(address, amount)?,
+            ));
+        }
+        if let Some((index, data)) = indexation {
+            essence_builder = essence_builder.with_payload(Payload::Indexation(Box::new(
+                IndexationPayload::new(index.as_bytes(), data)?,
+            )));
+        }
+        let essence = Essence::Regular(essence_builder.finish()?);
+        let hashed_essence = essence.hash();
+
+        // sign the essence, inputs on the same address reference the first signature
+        let mut unlock_blocks = Vec::new();
+        let mut signature_indexes: HashMap<Address, u16> = HashMap::new();
+        for (input_index, address) in input_addresses.iter().enumerate() {
+            if let Some(signature_index) = signature_indexes.get(address) {
+                unlock_blocks.push(UnlockBlock::Reference(ReferenceUnlock::new(
+                    *signature_index,
+                )?));
+                continue;
+            }
+            let secret_key = (0..ADDRESS_SEARCH_RANGE)
+                .map(|address_index| derive_secret_key(&seed, address_index))
+                .find(|secret_key| match secret_key {
+                    Ok(secret_key) => address_from_secret_key(secret_key) == *address,
+                    Err(_) => true,
+                })
+                .ok_or(crate::error::Error::UtxoInputNotFound)??;
+            let public_key = secret_key.public_key().to_compressed_bytes();
+            let signature = Box::new(secret_key.sign(&hashed_essence).to_bytes());
+            unlock_blocks.push(UnlockBlock::Signature(SignatureUnlock::Ed25519(
+                Ed25519Signature::new(public_key, signature),
+            )));
+            signature_indexes.insert(*address, input_index as u16);
+        }
+
+        let transaction = TransactionPayload::builder()
+            .with_essence(essence)
+            .with_unlock_blocks(UnlockBlocks::new(unlock_blocks)?)
+            .finish()?;
+        self.build_message(Payload::Transaction(Box::new(transaction)))
+    }
+
+    async fn post_message(&self, message: &Message) -> Result<MessageId> {
+        let mut state = self.state.lock().expect("Simulated ledger lock poisoned");
+        let message_id = message.id().0;
+        if state.post_failure == Some(PostFailure::Rejected) {
+            return Err(crate::error::Error::SimulatedNodeFailure(
+                "message rejected",
+            ));
+        }
+        if !state.messages.contains_key(&message_id) {
+            // update the ledger state, a transaction with a spent input conflicts
+            if let Some(Payload::Transaction(transaction)) = message.payload() {
+                let Essence::Regular(essence) = transaction.essence();
+                let mut inputs = Vec::new();
+                for input in essence.inputs() {
+                    match input {
+                        Input::Utxo(utxo)
+                            if state.unspent_outputs.contains_key(utxo.output_id()) =>
+                        {
+                            inputs.push(*utxo.output_id())
+                        }
+                        _ => return Err(crate::error::Error::UtxoInputNotFound),
+                    }
+                }
+                for input in &inputs {
+                    state.unspent_outputs.remove(input);
+                }
+                for (index, output) in essence.outputs().iter().enumerate() {
+                    state.unspent_outputs.insert(
+                        OutputId::new(transaction.id(), index as u16)?,
+                        output.clone(),
+                    );
+                }
+            }
+            state.insert_message(message);
+        }
+        if state.post_failure == Some(PostFailure::ResponseLost) {
+            return Err(crate::error::Error::SimulatedNodeFailure("response lost"));
+        }
+        Ok(message_id)
+    }
+
+    async fn wait_until_included(&self, message_id: &MessageId) -> Result<()> {
+        // messages are included instantly
+        self.get_message(message_id).await.map(|_| ())
+    }
+
+    async fn get_message_metadata(&self, message_id: &MessageId) -> Result<MessageMetadata> {
+        let state = self.state.lock().expect("Simulated ledger lock poisoned");
+        state
+            .metadata
+            .get(message_id)
+            .cloned()
+            .ok_or(crate::error::Error::MessageNotFound)
+    }
+
+    async fn get_milestone(&self, index: u32) -> Result<MilestoneInfo> {
+        let state = self.state.lock().expect("Simulated ledger lock poisoned");
+        index
+            .checked_sub(1)
+            .and_then(|position| state.milestones.get(position as usize))
+            .cloned()
+            .ok_or(crate::error::Error::RecordNotFound)
+    }
+}
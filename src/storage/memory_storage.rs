@@ -0,0 +1,85 @@
+use super::{Storage, StorageBatch, StorageColumn};
+use crate::error::Result;
+use async_trait::async_trait;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+};
+
+/// Storage that keeps the records only in memory, for tests and Chronists that don't need to survive a restart
+#[derive(Default)]
+pub struct MemoryStorage {
+    columns: HashMap<StorageColumn, BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    // Records of a column ordered by key, starting at the bound
+    fn range(
+        &self,
+        column: StorageColumn,
+        start: Bound<Vec<u8>>,
+    ) -> impl Iterator<Item = (&Vec<u8>, &Vec<u8>)> {
+        self.columns
+            .get(&column)
+            .into_iter()
+            .flat_map(move |records| records.range((start.clone(), Bound::Unbounded)))
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn get_record(&self, column: StorageColumn, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .columns
+            .get(&column)
+            .and_then(|records| records.get(key))
+            .cloned())
+    }
+
+    async fn write_batch(&mut self, batch: StorageBatch) -> Result<()> {
+        for (column, key, record) in batch.into_records() {
+            let records = self.columns.entry(column).or_default();
+            match record {
+                Some(record) => records.insert(key, record),
+                None => records.remove(&key),
+            };
+        }
+        Ok(())
+    }
+
+    async fn prefix_records(
+        &self,
+        column: StorageColumn,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let start = match after {
+            Some(key) => Bound::Excluded(key.to_vec()),
+            None => Bound::Included(prefix.to_vec()),
+        };
+        Ok(self
+            .range(column, start)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .take(limit)
+            .map(|(key, record)| (key.clone(), record.clone()))
+            .collect())
+    }
+
+    async fn records_after(
+        &self,
+        column: StorageColumn,
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let start = match after {
+            Some(key) => Bound::Excluded(key.to_vec()),
+            None => Bound::Unbounded,
+        };
+        Ok(self
+            .range(column, start)
+            .take(limit)
+            .map(|(key, record)| (key.clone(), record.clone()))
+            .collect())
+    }
+}
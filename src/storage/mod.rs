@@ -0,0 +1,335 @@
+mod memory_storage;
+#[cfg(feature = "rocksdb")]
+mod rocksdb_storage;
+#[cfg(feature = "sqlite")]
+mod sqlite_storage;
+
+pub use memory_storage::MemoryStorage;
+#[cfg(feature = "rocksdb")]
+pub use rocksdb_storage::RocksdbStorage;
+#[cfg(feature = "sqlite")]
+pub use sqlite_storage::SqliteStorage;
+
+use crate::{
+    chronist::ContentHash,
+    error::{Error, Result},
+};
+use async_trait::async_trait;
+use iota_client::bee_message::MessageId;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{convert::TryInto, path::PathBuf, str::FromStr};
+
+// Key prefixes of dbs created before the column families, the records get moved at the first start
+const LEGACY_POSITION_PREFIX: &str = "inclusion_index";
+const LEGACY_TRANSACTION_MESSAGE_PREFIX: &str = "transaction_message";
+const CALLBACK_PREFIX: &str = "callback";
+/// Records that are read at once when all records of a prefix are needed, so a column is never loaded completely
+pub(crate) const PAGE_SIZE: usize = 1000;
+
+/// Column families of the db, records of the same kind are stored together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StorageColumn {
+    /// `MessageWrapper`s by message id
+    Messages,
+    /// `UtxoData` by position, the positions are big endian encoded so they're iterated in order
+    Positions,
+    /// Messages with the transaction of a position by message id
+    TransactionMessages,
+    /// `HashWrapper`s by algorithm tag and digest
+    ContentHashes,
+    /// Merkle trees by position
+    MerkleTrees,
+    /// Inclusion position by message id of the included messages
+    IncludedMessageIds,
+    /// `PendingEntry`s of the message ids that wait to be included by message id
+    PendingMessageIds,
+    /// Hashes that wait to be included by algorithm tag and digest
+    PendingHashes,
+    /// Config, transaction index, callbacks and the webhook queue
+    Metadata,
+}
+
+impl StorageColumn {
+    pub const ALL: [StorageColumn; 9] = [
+        StorageColumn::Messages,
+        StorageColumn::Positions,
+        StorageColumn::TransactionMessages,
+        StorageColumn::ContentHashes,
+        StorageColumn::MerkleTrees,
+        StorageColumn::IncludedMessageIds,
+        StorageColumn::PendingMessageIds,
+        StorageColumn::PendingHashes,
+        StorageColumn::Metadata,
+    ];
+
+    /// Name of the column family or table
+    pub fn name(self) -> &'static str {
+        match self {
+            StorageColumn::Messages => "messages",
+            StorageColumn::Positions => "positions",
+            StorageColumn::TransactionMessages => "transaction_messages",
+            StorageColumn::ContentHashes => "content_hashes",
+            StorageColumn::MerkleTrees => "merkle_trees",
+            StorageColumn::IncludedMessageIds => "included_message_ids",
+            StorageColumn::PendingMessageIds => "pending_message_ids",
+            StorageColumn::PendingHashes => "pending_hashes",
+            StorageColumn::Metadata => "metadata",
+        }
+    }
+}
+
+/// Key of a record, it determines the column family and the encoding of the key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKey {
+    Message(MessageId),
+    Position(u64),
+    TransactionMessage(MessageId),
+    ContentHash(ContentHash),
+    MerkleTree(u64),
+    IncludedMessageId(MessageId),
+    PendingMessageId(MessageId),
+    PendingHash(ContentHash),
+    /// Callback urls of a message
+    Callback(MessageId),
+    TransactionIndex,
+    /// Position of the latest transaction that is known to be confirmed, the callbacks of its messages and of the
+    /// messages of the transactions before it got enqueued
+    ConfirmedTransactionIndex,
+    ChronistConfig,
+    /// List of the included message ids in dbs created before they were stored with `IncludedMessageId`
+    MessageIds,
+    PendingTransaction,
+    WebhookQueue,
+    /// If transactions commit to a Merkle root, see `Chronist::use_merkle_tree`
+    MerkleTreeEnabled,
+}
+
+impl StorageKey {
+    pub fn column(&self) -> StorageColumn {
+        match self {
+            StorageKey::Message(_) => StorageColumn::Messages,
+            StorageKey::Position(_) => StorageColumn::Positions,
+            StorageKey::TransactionMessage(_) => StorageColumn::TransactionMessages,
+            StorageKey::ContentHash(_) => StorageColumn::ContentHashes,
+            StorageKey::MerkleTree(_) => StorageColumn::MerkleTrees,
+            StorageKey::IncludedMessageId(_) => StorageColumn::IncludedMessageIds,
+            StorageKey::PendingMessageId(_) => StorageColumn::PendingMessageIds,
+            StorageKey::PendingHash(_) => StorageColumn::PendingHashes,
+            _ => StorageColumn::Metadata,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            StorageKey::Message(message_id)
+            | StorageKey::TransactionMessage(message_id)
+            | StorageKey::IncludedMessageId(message_id)
+            | StorageKey::PendingMessageId(message_id) => message_id.as_ref().to_vec(),
+            StorageKey::Position(position) | StorageKey::MerkleTree(position) => {
+                position.to_be_bytes().to_vec()
+            }
+            // the same digest can be stored for different algorithms
+            StorageKey::ContentHash(hash) | StorageKey::PendingHash(hash) => {
+                let mut key = vec![hash.algorithm.tag()];
+                key.extend_from_slice(&hash.digest);
+                key
+            }
+            // same keys as before the column families, so the metadata records can be moved unchanged
+            StorageKey::Callback(message_id) => {
+                format!("{}{}", CALLBACK_PREFIX, message_id).into_bytes()
+            }
+            StorageKey::TransactionIndex => b"transaction_index".to_vec(),
+            StorageKey::ConfirmedTransactionIndex => b"confirmed_transaction_index".to_vec(),
+            StorageKey::ChronistConfig => b"chronist_config".to_vec(),
+            StorageKey::MessageIds => b"message_ids".to_vec(),
+            StorageKey::PendingTransaction => b"pending_transaction".to_vec(),
+            StorageKey::WebhookQueue => b"webhook_queue".to_vec(),
+            StorageKey::MerkleTreeEnabled => b"merkle_tree_enabled".to_vec(),
+        }
+    }
+
+    // Prefix of the keys of the callback urls in the metadata column
+    pub(crate) fn callback_prefix() -> &'static [u8] {
+        CALLBACK_PREFIX.as_bytes()
+    }
+
+    // Message id of a callback key
+    pub(crate) fn callback_message_id(key: &[u8]) -> Option<MessageId> {
+        let key = std::str::from_utf8(key).ok()?;
+        MessageId::from_str(key.strip_prefix(CALLBACK_PREFIX)?).ok()
+    }
+
+    // Key of a record from the default column family of a db created before the column families
+    pub(crate) fn from_legacy_key(key: &str) -> Option<Self> {
+        if let Ok(message_id) = MessageId::from_str(key) {
+            return Some(StorageKey::Message(message_id));
+        }
+        if let Some(position) = key.strip_prefix(LEGACY_POSITION_PREFIX) {
+            return position.parse().ok().map(StorageKey::Position);
+        }
+        if let Some(message_id) = key.strip_prefix(LEGACY_TRANSACTION_MESSAGE_PREFIX) {
+            return MessageId::from_str(message_id)
+                .ok()
+                .map(StorageKey::TransactionMessage);
+        }
+        None
+    }
+}
+
+/// Decode the key of a record in `StorageColumn::Messages`, `StorageColumn::TransactionMessages`,
+/// `StorageColumn::IncludedMessageIds` or `StorageColumn::PendingMessageIds`
+pub(crate) fn decode_message_id(key: &[u8]) -> Result<MessageId> {
+    let bytes: [u8; 32] = key
+        .try_into()
+        .map_err(|_| Error::InvalidParameter(format!("invalid message id key {:?}", key)))?;
+    Ok(MessageId::new(bytes))
+}
+
+/// Records that are written together with `Storage::write_batch`, either all or none of them get written
+#[derive(Default)]
+pub struct StorageBatch {
+    // None deletes the key
+    records: Vec<(StorageColumn, Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl StorageBatch {
+    pub fn set<T: Serialize + ?Sized>(&mut self, key: StorageKey, record: &T) -> Result<()> {
+        self.records.push((
+            key.column(),
+            key.to_bytes(),
+            Some(serde_json::to_vec(record)?),
+        ));
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: StorageKey) {
+        self.records.push((key.column(), key.to_bytes(), None));
+    }
+
+    /// Column, key and the JSON encoded record of each write, None for deleted keys
+    pub fn into_records(self) -> Vec<(StorageColumn, Vec<u8>, Option<Vec<u8>>)> {
+        self.records
+    }
+}
+
+/// Key value storage of the Chronist, the records are grouped in columns and ordered by their key bytes
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Get the record of a key, None if it doesn't exist
+    async fn get_record(&self, column: StorageColumn, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Write all records of the batch atomically
+    async fn write_batch(&mut self, batch: StorageBatch) -> Result<()>;
+
+    /// Up to `limit` records of a column with keys that start with the prefix ordered by key, starting after the key
+    /// `after` or at the first key with the prefix
+    async fn prefix_records(
+        &self,
+        column: StorageColumn,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    /// Up to `limit` records of a column ordered by key, starting after the key `after` or at the first key
+    async fn records_after(
+        &self,
+        column: StorageColumn,
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+}
+
+/// Typed access to the JSON encoded records of a `Storage`
+#[async_trait]
+pub(crate) trait StorageExt: Storage {
+    async fn get<T: DeserializeOwned>(&self, key: &StorageKey) -> Result<T> {
+        match self.get_record(key.column(), &key.to_bytes()).await? {
+            Some(record) => Ok(serde_json::from_slice(&record)?),
+            None => Err(Error::RecordNotFound),
+        }
+    }
+
+    async fn set<T: Serialize + Sync + ?Sized>(
+        &mut self,
+        key: &StorageKey,
+        record: &T,
+    ) -> Result<()> {
+        let mut batch = StorageBatch::default();
+        batch.set(*key, record)?;
+        self.write_batch(batch).await
+    }
+
+    async fn iter_prefix<T: DeserializeOwned>(
+        &self,
+        column: StorageColumn,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, T)>> {
+        self.prefix_records(column, prefix, after, limit)
+            .await?
+            .into_iter()
+            .map(|(key, record)| -> Result<(Vec<u8>, T)> {
+                Ok((key, serde_json::from_slice(&record)?))
+            })
+            .collect()
+    }
+
+    async fn iter_after<T: DeserializeOwned>(
+        &self,
+        column: StorageColumn,
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, T)>> {
+        self.records_after(column, after, limit)
+            .await?
+            .into_iter()
+            .map(|(key, record)| -> Result<(Vec<u8>, T)> {
+                Ok((key, serde_json::from_slice(&record)?))
+            })
+            .collect()
+    }
+}
+
+impl<S: Storage + ?Sized> StorageExt for S {}
+
+/// Storage backend of the Chronist, see `Chronist::with_storage`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageConfig {
+    /// RocksDB in the directory at the path
+    #[cfg(feature = "rocksdb")]
+    Rocksdb(PathBuf),
+    /// SQLite db file at the path
+    #[cfg(feature = "sqlite")]
+    Sqlite(PathBuf),
+    /// Records are only kept in memory and get lost when the Chronist is dropped, for tests
+    Memory,
+}
+
+impl StorageConfig {
+    /// Config from the name of the backend (`rocksdb` or `sqlite`) and the path of the db. The memory storage
+    /// can't be selected by name, a server with it would lose all proofs at a restart
+    pub fn from_name(name: &str, path: &str) -> Result<Self> {
+        match name {
+            #[cfg(feature = "rocksdb")]
+            "rocksdb" => Ok(StorageConfig::Rocksdb(PathBuf::from(path))),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(StorageConfig::Sqlite(PathBuf::from(path))),
+            _ => Err(Error::InvalidParameter(format!(
+                "unsupported storage backend {}",
+                name
+            ))),
+        }
+    }
+
+    pub fn open(&self) -> Result<Box<dyn Storage>> {
+        Ok(match self {
+            #[cfg(feature = "rocksdb")]
+            StorageConfig::Rocksdb(path) => Box::new(RocksdbStorage::new(path)?),
+            #[cfg(feature = "sqlite")]
+            StorageConfig::Sqlite(path) => Box::new(SqliteStorage::new(path)?),
+            StorageConfig::Memory => Box::new(MemoryStorage::default()),
+        })
+    }
+}
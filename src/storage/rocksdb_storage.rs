@@ -0,0 +1,125 @@
+use super::{Storage, StorageBatch, StorageColumn, StorageKey};
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use rocksdb::{DBCompressionType, Direction, IteratorMode, Options, WriteBatch, DB};
+use std::{path::Path, sync::Arc};
+use tokio::sync::Mutex;
+
+/// Storage in RocksDB, the records are stored as JSON in the column family of their key.
+pub struct RocksdbStorage {
+    db: Arc<Mutex<DB>>,
+}
+
+impl RocksdbStorage {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let mut opts = Options::default();
+        opts.set_compression_type(DBCompressionType::Lz4);
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let db = DB::open_cf(
+            &opts,
+            path,
+            StorageColumn::ALL.iter().map(|column| column.name()),
+        )?;
+        migrate_default_column_family(&db)?;
+        Ok(Self {
+            db: Arc::new(Mutex::new(db)),
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for RocksdbStorage {
+    async fn get_record(&self, column: StorageColumn, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let db = self.db.lock().await;
+        Ok(db.get_cf(column_family(&db, column)?, key)?)
+    }
+
+    async fn write_batch(&mut self, batch: StorageBatch) -> Result<()> {
+        let db = self.db.lock().await;
+        let mut write_batch = WriteBatch::default();
+        for (column, key, record) in batch.into_records() {
+            let cf = column_family(&db, column)?;
+            match record {
+                Some(record) => write_batch.put_cf(cf, key, record),
+                None => write_batch.delete_cf(cf, key),
+            }
+        }
+        db.write(write_batch)?;
+        Ok(())
+    }
+
+    async fn prefix_records(
+        &self,
+        column: StorageColumn,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let db = self.db.lock().await;
+        Ok(db
+            .iterator_cf(
+                column_family(&db, column)?,
+                IteratorMode::From(after.unwrap_or(prefix), Direction::Forward),
+            )
+            .skip_while(|(key, _)| Some(&key[..]) == after)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .take(limit)
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect())
+    }
+
+    async fn records_after(
+        &self,
+        column: StorageColumn,
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let db = self.db.lock().await;
+        let mode = match after {
+            Some(key) => IteratorMode::From(key, Direction::Forward),
+            None => IteratorMode::Start,
+        };
+        Ok(db
+            .iterator_cf(column_family(&db, column)?, mode)
+            .skip_while(|(key, _)| Some(&key[..]) == after)
+            .take(limit)
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect())
+    }
+}
+
+fn column_family(db: &DB, column: StorageColumn) -> Result<&rocksdb::ColumnFamily> {
+    db.cf_handle(column.name())
+        .ok_or_else(|| Error::InvalidParameter(format!("missing column family {}", column.name())))
+}
+
+// Move the records of a db created before the column families from the default column family to the column family
+// of their key, in a single write batch
+fn migrate_default_column_family(db: &DB) -> Result<()> {
+    let mut write_batch = WriteBatch::default();
+    let mut migrated = 0;
+    for (key, value) in db.iterator(IteratorMode::Start) {
+        let legacy_key = String::from_utf8_lossy(&key).to_string();
+        match StorageKey::from_legacy_key(&legacy_key) {
+            Some(storage_key) => write_batch.put_cf(
+                column_family(db, storage_key.column())?,
+                storage_key.to_bytes(),
+                value,
+            ),
+            // metadata keys didn't change
+            None => write_batch.put_cf(
+                column_family(db, StorageColumn::Metadata)?,
+                key.clone(),
+                value,
+            ),
+        }
+        write_batch.delete(key);
+        migrated += 1;
+    }
+    if migrated > 0 {
+        db.write(write_batch)?;
+        println!("Moved {} records to the column families", migrated);
+    }
+    Ok(())
+}
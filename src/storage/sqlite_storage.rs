@@ -0,0 +1,118 @@
+use super::{Storage, StorageBatch, StorageColumn};
+use crate::error::Result;
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{convert::TryFrom, path::Path};
+use tokio::sync::Mutex;
+
+/// Storage in a SQLite db file with a table for each column, for small deployments without RocksDB
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        for column in StorageColumn::ALL.iter() {
+            // blobs are compared with memcmp, so the keys are ordered like in RocksDB
+            connection.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (key BLOB PRIMARY KEY NOT NULL, value BLOB NOT NULL) WITHOUT ROWID",
+                    column.name()
+                ),
+                params![],
+            )?;
+        }
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn get_record(&self, column: StorageColumn, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .connection
+            .lock()
+            .await
+            .query_row(
+                &format!("SELECT value FROM {} WHERE key = ?1", column.name()),
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    async fn write_batch(&mut self, batch: StorageBatch) -> Result<()> {
+        let mut connection = self.connection.lock().await;
+        let transaction = connection.transaction()?;
+        for (column, key, record) in batch.into_records() {
+            match record {
+                Some(record) => transaction.execute(
+                    &format!(
+                        "INSERT OR REPLACE INTO {} (key, value) VALUES (?1, ?2)",
+                        column.name()
+                    ),
+                    params![key, record],
+                )?,
+                None => transaction.execute(
+                    &format!("DELETE FROM {} WHERE key = ?1", column.name()),
+                    params![key],
+                )?,
+            };
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    async fn prefix_records(
+        &self,
+        column: StorageColumn,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let connection = self.connection.lock().await;
+        // a negative limit means no limit in SQLite
+        let limit = i64::try_from(limit).unwrap_or(-1);
+        // all keys are greater than an empty blob
+        let after = after.unwrap_or(&[]);
+        let mut statement = connection.prepare(&format!(
+            "SELECT key, value FROM {} WHERE key >= ?1 AND key > ?2 ORDER BY key LIMIT ?3",
+            column.name()
+        ))?;
+        let rows = statement.query_map(params![prefix, after, limit], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        let mut records = Vec::new();
+        for row in rows {
+            let (key, record): (Vec<u8>, Vec<u8>) = row?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            records.push((key, record));
+        }
+        Ok(records)
+    }
+
+    async fn records_after(
+        &self,
+        column: StorageColumn,
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let connection = self.connection.lock().await;
+        // a negative limit means no limit in SQLite
+        let limit = i64::try_from(limit).unwrap_or(-1);
+        // all keys are greater than an empty blob
+        let after = after.unwrap_or(&[]);
+        let mut statement = connection.prepare(&format!(
+            "SELECT key, value FROM {} WHERE key > ?1 ORDER BY key LIMIT ?2",
+            column.name()
+        ))?;
+        let rows =
+            statement.query_map(params![after, limit], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<(Vec<u8>, Vec<u8>)>>>()?)
+    }
+}
@@ -1,18 +1,437 @@
 use crate::{
-    chronist::InclusionData, error::Result, inclusion_proof::InclusionProof,
+    chronist::{InclusionData, INCLUSION_DATA_VERSION},
+    error::Result,
+    inclusion_proof::{HashInclusionProof, InclusionProof, MilestoneAnchor, PROOF_VERSION},
     iota_api::is_output_known,
+    ledger::{
+        address_from_public_key, output_address_and_amount, LedgerBackend, LedgerInclusionState,
+    },
+    merkle_tree::{content_hash_leaf, message_id_leaf, MerkleHash, MerkleProof, MerkleRoot},
 };
 use iota_client::{
-    bee_message::prelude::{Essence, Input, Payload, TransactionPayload},
-    Client,
+    bee_message::prelude::{
+        Address, Essence, Input, OutputId, Payload, SignatureUnlock, TransactionPayload,
+        UnlockBlock,
+    },
+    crypto::signatures::ed25519::{PublicKey, Signature},
 };
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// Status of a single check of a verification
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// Can't be checked without a node
+    Unverified,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn from_result(result: Result<()>, passed_detail: String) -> Self {
+        match result {
+            Ok(()) => Self {
+                status: CheckStatus::Passed,
+                detail: passed_detail,
+            },
+            Err(e) => Self {
+                status: CheckStatus::Failed,
+                detail: e.to_string(),
+            },
+        }
+    }
+}
+
+/// Report of everything that can be verified from the proof alone, without a node
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OfflineVerificationReport {
+    /// Version of the proof is supported
+    pub proof_version: CheckResult,
+    /// Message id is part of the indexation payload of the first transaction
+    pub message_id_included: CheckResult,
+    /// One output of each transaction is used as input in the next transaction
+    pub transaction_chain: CheckResult,
+    /// Signatures and unlock blocks of each transaction
+    pub transaction_signatures: Vec<CheckResult>,
+    /// latest_output_id is an output of the last transaction
+    pub latest_output: CheckResult,
+    /// Latest output is unspent, this is an assumption that needs to be checked with a node
+    pub latest_output_unspent: CheckResult,
+    /// Milestone anchor of the proof, needs to be checked with a node or permanode
+    pub milestone_anchor: Option<CheckResult>,
+}
+
+impl OfflineVerificationReport {
+    /// True if all checks that can be done offline passed
+    pub fn is_valid(&self) -> bool {
+        self.proof_version.status == CheckStatus::Passed
+            && self.message_id_included.status == CheckStatus::Passed
+            && self.transaction_chain.status == CheckStatus::Passed
+            && self
+                .transaction_signatures
+                .iter()
+                .all(|check| check.status == CheckStatus::Passed)
+            && self.latest_output.status == CheckStatus::Passed
+    }
+}
+
+/// Step of the proof validation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum ProofStepKind {
+    /// Version of the proof is supported
+    ProofVersion,
+    /// Message id is part of the indexation payload of the first transaction
+    MessageIdFound,
+    /// Hash is part of the indexation payload of the first transaction
+    HashFound,
+    /// An output of the transaction `from` is used as input in the transaction `to`
+    ChainLink { from: usize, to: usize },
+    /// latest_output_id is an output of the last transaction
+    LatestUtxoOwnership,
+    /// Latest output is known by a node
+    OutputLiveness,
+    /// Last transaction is referenced by the milestone of the proof, replaces the output liveness
+    MilestoneAnchor,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProofStep {
+    pub step: ProofStepKind,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl ProofStep {
+    fn new(step: ProofStepKind, result: Result<()>, passed_detail: String) -> Self {
+        let CheckResult { status, detail } = CheckResult::from_result(result, passed_detail);
+        Self {
+            step,
+            status,
+            detail,
+        }
+    }
+}
+
+/// Result of each step of the proof validation, so it's visible why a proof is invalid
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProofVerdict {
+    pub valid: bool,
+    pub steps: Vec<ProofStep>,
+}
+
+// Parts of a proof that are the same for message and hash proofs
+struct ProofChain<'a> {
+    version: u8,
+    latest_output_id: &'a OutputId,
+    transactions: &'a [TransactionPayload],
+    milestone_anchor: Option<&'a MilestoneAnchor>,
+    merkle_proof: Option<&'a MerkleProof>,
+}
+
+impl<'a> From<&'a InclusionProof> for ProofChain<'a> {
+    fn from(proof: &'a InclusionProof) -> Self {
+        Self {
+            version: proof.version,
+            latest_output_id: &proof.latest_output_id,
+            transactions: &proof.transactions,
+            milestone_anchor: proof.milestone_anchor.as_ref(),
+            merkle_proof: proof.merkle_proof.as_ref(),
+        }
+    }
+}
+
+impl<'a> From<&'a HashInclusionProof> for ProofChain<'a> {
+    fn from(proof: &'a HashInclusionProof) -> Self {
+        Self {
+            version: proof.version,
+            latest_output_id: &proof.latest_output_id,
+            transactions: &proof.transactions,
+            milestone_anchor: proof.milestone_anchor.as_ref(),
+            merkle_proof: proof.merkle_proof.as_ref(),
+        }
+    }
+}
+
+/// Function to validate all steps of a proof, other than `is_valid_proof` it doesn't stop at the first failed step
+pub async fn get_proof_verdict(ledger: &dyn LedgerBackend, proof: &InclusionProof) -> ProofVerdict {
+    let steps = vec![
+        ProofStep::new(
+            ProofStepKind::ProofVersion,
+            validate_proof_version(&proof.into()),
+            format!("Proof version {} is supported", proof.version),
+        ),
+        ProofStep::new(
+            ProofStepKind::MessageIdFound,
+            validate_message_id_included(proof),
+            format!(
+                "Message {} is included in the first transaction",
+                proof.message.id().0
+            ),
+        ),
+    ];
+    get_chain_verdict(ledger, &proof.into(), steps).await
+}
+
+/// Function to validate all steps of a hash proof
+pub async fn get_hash_proof_verdict(
+    ledger: &dyn LedgerBackend,
+    proof: &HashInclusionProof,
+) -> ProofVerdict {
+    let steps = vec![
+        ProofStep::new(
+            ProofStepKind::ProofVersion,
+            validate_proof_version(&proof.into()),
+            format!("Proof version {} is supported", proof.version),
+        ),
+        ProofStep::new(
+            ProofStepKind::HashFound,
+            validate_hash_included(proof),
+            format!(
+                "Hash {} is included in the first transaction",
+                proof.hash.to_hex()
+            ),
+        ),
+    ];
+    get_chain_verdict(ledger, &proof.into(), steps).await
+}
+
+// Adds the steps for the transactions and the latest output to the steps of the proof content
+async fn get_chain_verdict(
+    ledger: &dyn LedgerBackend,
+    proof: &ProofChain<'_>,
+    mut steps: Vec<ProofStep>,
+) -> ProofVerdict {
+    for (index, txs) in proof.transactions.windows(2).enumerate() {
+        steps.push(ProofStep::new(
+            ProofStepKind::ChainLink {
+                from: index,
+                to: index + 1,
+            },
+            validate_transaction_link(&txs[0], &txs[1]),
+            format!(
+                "Transaction {} uses an output of transaction {}",
+                txs[1].id(),
+                txs[0].id()
+            ),
+        ));
+    }
+    steps.push(ProofStep::new(
+        ProofStepKind::LatestUtxoOwnership,
+        validate_latest_output(proof),
+        format!(
+            "Output {} is part of the last transaction",
+            proof.latest_output_id
+        ),
+    ));
+    match proof.milestone_anchor {
+        Some(milestone_anchor) => steps.push(ProofStep::new(
+            ProofStepKind::MilestoneAnchor,
+            validate_milestone_anchor(ledger, proof, milestone_anchor).await,
+            format!(
+                "Last transaction is referenced by milestone {}",
+                milestone_anchor.milestone_index
+            ),
+        )),
+        None => {
+            let output_known = if is_output_known(ledger, proof.latest_output_id).await {
+                Ok(())
+            } else {
+                Err(crate::error::Error::OutputNotFound)
+            };
+            steps.push(ProofStep::new(
+                ProofStepKind::OutputLiveness,
+                output_known,
+                format!("Output {} is known by the node", proof.latest_output_id),
+            ));
+        }
+    }
+
+    ProofVerdict {
+        valid: steps.iter().all(|step| step.status == CheckStatus::Passed),
+        steps,
+    }
+}
+
+/// Function to verify everything of a proof that doesn't require a node
+// 1. Calculate message id and check if it's part of the indexation payload of the first transaction
+// 2. Check for each transaction if one output is used as input in the next transaction
+// 3. Check the signatures and unlock blocks of each transaction
+// 4. Check if the latest output is part of the last transaction
+// That the latest output is unspent can't be checked without a node and stays unverified
+pub fn verify_proof_offline(proof: &InclusionProof) -> OfflineVerificationReport {
+    let proof_version = CheckResult::from_result(
+        validate_proof_version(&proof.into()),
+        format!("Proof version {} is supported", proof.version),
+    );
+    let message_id_included = CheckResult::from_result(
+        validate_message_id_included(proof),
+        format!(
+            "Message {} is included in the first transaction",
+            proof.message.id().0
+        ),
+    );
+    let transaction_chain = CheckResult::from_result(
+        validate_transaction_chain(&proof.transactions),
+        format!(
+            "{} transactions reference each other",
+            proof.transactions.len()
+        ),
+    );
+    let transaction_signatures = proof
+        .transactions
+        .iter()
+        .enumerate()
+        .map(|(index, transaction)| {
+            let previous_transaction = match index {
+                0 => None,
+                _ => proof.transactions.get(index - 1),
+            };
+            CheckResult::from_result(
+                validate_transaction_signatures(transaction, previous_transaction),
+                format!("Transaction {} is signed correctly", transaction.id()),
+            )
+        })
+        .collect();
+    let latest_output = CheckResult::from_result(
+        validate_latest_output(&proof.into()),
+        format!(
+            "Output {} is part of the last transaction",
+            proof.latest_output_id
+        ),
+    );
+    let latest_output_unspent = CheckResult {
+        status: CheckStatus::Unverified,
+        detail: format!(
+            "Output {} needs to be checked with a node",
+            proof.latest_output_id
+        ),
+    };
+
+    let milestone_anchor = proof
+        .milestone_anchor
+        .as_ref()
+        .map(|milestone_anchor| CheckResult {
+            status: CheckStatus::Unverified,
+            detail: format!(
+                "Milestone {} needs to be checked with a node or permanode",
+                milestone_anchor.milestone_index
+            ),
+        });
+
+    OfflineVerificationReport {
+        proof_version,
+        message_id_included,
+        transaction_chain,
+        transaction_signatures,
+        latest_output,
+        latest_output_unspent,
+        milestone_anchor,
+    }
+}
 
 /// Function to validate the structure of the proof and check if the latest output is known
 // 1. Calculate message id and check if it's part of the indexation payload of the first transaction
 // 2. Check for each transaction if one output is used as input in the next transaction
 // 3. Check if latest output is known by a node
-pub async fn is_valid_proof(iota_client: &Client, proof: &InclusionProof) -> Result<bool> {
+pub async fn is_valid_proof(ledger: &dyn LedgerBackend, proof: &InclusionProof) -> Result<bool> {
+    validate_proof_version(&proof.into())?;
     // 1. check if message id is part of the first indexation payload
+    validate_message_id_included(proof)?;
+    is_valid_chain(ledger, &proof.into()).await
+}
+
+/// Function to validate a hash proof, same as `is_valid_proof` but with the hash instead of the message id
+pub async fn is_valid_hash_proof(
+    ledger: &dyn LedgerBackend,
+    proof: &HashInclusionProof,
+) -> Result<bool> {
+    validate_proof_version(&proof.into())?;
+    validate_hash_included(proof)?;
+    is_valid_chain(ledger, &proof.into()).await
+}
+
+async fn is_valid_chain(ledger: &dyn LedgerBackend, proof: &ProofChain<'_>) -> Result<bool> {
+    // 2. check for each transaction if one output is used as input in the next transaction
+    validate_transaction_chain(proof.transactions)?;
+
+    // Check if latest_output_id is part of the latest transaction
+    validate_latest_output(proof)?;
+
+    // 3. check if latest output is known by the node, or if the proof is anchored, if the milestone referenced the
+    // last transaction
+    match proof.milestone_anchor {
+        Some(milestone_anchor) => {
+            validate_milestone_anchor(ledger, proof, milestone_anchor).await?;
+            Ok(true)
+        }
+        None => Ok(is_output_known(ledger, proof.latest_output_id).await),
+    }
+}
+
+// Checks if the message of the milestone anchor has the last transaction and was referenced by the milestone
+async fn validate_milestone_anchor(
+    ledger: &dyn LedgerBackend,
+    proof: &ProofChain<'_>,
+    milestone_anchor: &MilestoneAnchor,
+) -> Result<()> {
+    let latest_transaction = proof
+        .transactions
+        .last()
+        .ok_or(crate::error::Error::NoTransactionPayload)?;
+    let message = ledger.get_message(&milestone_anchor.message_id).await?;
+    match message.payload() {
+        Some(Payload::Transaction(tx)) if tx.id() == latest_transaction.id() => {}
+        _ => {
+            return Err(crate::error::Error::InvalidMilestoneAnchor(
+                "message doesn't have the last transaction".to_string(),
+            ))
+        }
+    }
+
+    let metadata = ledger
+        .get_message_metadata(&milestone_anchor.message_id)
+        .await?;
+    if metadata.referenced_by_milestone_index != Some(milestone_anchor.milestone_index)
+        || metadata.ledger_inclusion_state != Some(LedgerInclusionState::Included)
+    {
+        return Err(crate::error::Error::InvalidMilestoneAnchor(format!(
+            "message isn't included by milestone {}",
+            milestone_anchor.milestone_index
+        )));
+    }
+
+    let milestone = ledger
+        .get_milestone(milestone_anchor.milestone_index)
+        .await?;
+    if milestone.timestamp != milestone_anchor.milestone_timestamp {
+        return Err(crate::error::Error::InvalidMilestoneAnchor(
+            "milestone timestamp doesn't match".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Checks if the proof version is supported and the proof only uses features of its version
+fn validate_proof_version(proof: &ProofChain<'_>) -> Result<()> {
+    match proof.version {
+        0 if proof.milestone_anchor.is_some() => Err(crate::error::Error::InvalidMilestoneAnchor(
+            "version 0 proofs can't have a milestone anchor".to_string(),
+        )),
+        0 if proof.merkle_proof.is_some() => Err(crate::error::Error::InvalidMerkleProof),
+        0..=PROOF_VERSION => Ok(()),
+        version => Err(crate::error::Error::UnsupportedProofVersion(version)),
+    }
+}
+
+// Checks if the message id is part of the indexation payload of the first transaction
+fn validate_message_id_included(proof: &InclusionProof) -> Result<()> {
     let msg_id = proof.message.id().0;
     let inclusion_data = get_inclusion_data(
         proof
@@ -20,26 +439,57 @@ pub async fn is_valid_proof(iota_client: &Client, proof: &InclusionProof) -> Res
             .first()
             .ok_or(crate::error::Error::NoTransactionPayload)?,
     )?;
-    if !inclusion_data.message_ids.contains(&msg_id) {
-        return Err(crate::error::Error::MessageIdNotInTransaction);
+    match (&inclusion_data.merkle_root, &proof.merkle_proof) {
+        (Some(merkle_root), Some(merkle_proof)) => {
+            validate_merkle_proof(merkle_root, merkle_proof, message_id_leaf(&msg_id))
+        }
+        _ if inclusion_data.message_ids.contains(&msg_id) => Ok(()),
+        _ => Err(crate::error::Error::MessageIdNotInTransaction),
     }
-    // 2. check for each transaction if one output is used as input in the next transaction
-    validate_transaction_chain(&proof.transactions)?;
+}
 
-    // Check if latest_output_id is part of the latest transaction
-    if proof
+// Checks if the hash is part of the indexation payload of the first transaction
+fn validate_hash_included(proof: &HashInclusionProof) -> Result<()> {
+    let inclusion_data = get_inclusion_data(
+        proof
+            .transactions
+            .first()
+            .ok_or(crate::error::Error::NoTransactionPayload)?,
+    )?;
+    match (&inclusion_data.merkle_root, &proof.merkle_proof) {
+        (Some(merkle_root), Some(merkle_proof)) => {
+            validate_merkle_proof(merkle_root, merkle_proof, content_hash_leaf(&proof.hash))
+        }
+        _ if inclusion_data.hashes.contains(&proof.hash) => Ok(()),
+        _ => Err(crate::error::Error::HashNotInTransaction),
+    }
+}
+
+// Checks if the audit path leads from the leaf to the Merkle root
+fn validate_merkle_proof(
+    merkle_root: &MerkleRoot,
+    merkle_proof: &MerkleProof,
+    leaf: MerkleHash,
+) -> Result<()> {
+    if merkle_proof.root(leaf) != merkle_root.root {
+        return Err(crate::error::Error::InvalidMerkleProof);
+    }
+    Ok(())
+}
+
+// Checks if latest_output_id is an output of the latest transaction
+fn validate_latest_output(proof: &ProofChain<'_>) -> Result<()> {
+    let latest_transaction = proof
         .transactions
         .last()
-        // Save to unwrap because we checked already if it's empty
-        .ok_or(crate::error::Error::NoTransactionPayload)?
-        .id()
-        != *proof.latest_output_id.transaction_id()
+        .ok_or(crate::error::Error::NoTransactionPayload)?;
+    let Essence::Regular(essence) = latest_transaction.essence();
+    if latest_transaction.id() != *proof.latest_output_id.transaction_id()
+        || essence.outputs().len() <= proof.latest_output_id.index() as usize
     {
         return Err(crate::error::Error::InvalidLatestUTXO);
     }
-
-    // 3. check if latest output is known by the node
-    Ok(is_output_known(iota_client, &proof.latest_output_id).await)
+    Ok(())
 }
 
 fn get_inclusion_data(tx_payload: &TransactionPayload) -> Result<InclusionData> {
@@ -48,7 +498,13 @@ fn get_inclusion_data(tx_payload: &TransactionPayload) -> Result<InclusionData>
         Some(Payload::Indexation(indexation_payload)) => {
             let data = String::from_utf8(indexation_payload.data().to_vec())?;
             let transaction_data: InclusionData = serde_json::from_str(&data)?;
-            Ok(transaction_data)
+            match transaction_data.version {
+                // version 0 has the same fields, only without the version, hashes and Merkle root
+                0..=INCLUSION_DATA_VERSION => Ok(transaction_data),
+                version => Err(crate::error::Error::UnsupportedInclusionDataVersion(
+                    version,
+                )),
+            }
         }
         _ => Err(crate::error::Error::NoIndexationPayload),
     }
@@ -56,15 +512,91 @@ fn get_inclusion_data(tx_payload: &TransactionPayload) -> Result<InclusionData>
 
 // Checks for each transaction if one output is used as input in the next transaction
 fn validate_transaction_chain(transactions: &[TransactionPayload]) -> Result<()> {
-    // Check if output from previous tx is used as input in next tx
     for txs in transactions.windows(2) {
-        let Essence::Regular(next_essence) = txs[1].essence();
+        validate_transaction_link(&txs[0], &txs[1])?;
+    }
+    Ok(())
+}
+
+// Check if output from previous tx is used as input in next tx
+fn validate_transaction_link(
+    previous_transaction: &TransactionPayload,
+    next_transaction: &TransactionPayload,
+) -> Result<()> {
+    let Essence::Regular(next_essence) = next_transaction.essence();
+
+    if !next_essence.inputs().iter().any(|input| match input {
+        Input::Utxo(utxo) => utxo.output_id().transaction_id() == &previous_transaction.id(),
+        _ => false,
+    }) {
+        return Err(crate::error::Error::InvalidMessageChain);
+    }
+    Ok(())
+}
+
+// Checks that the signatures of a transaction are valid and all of them belong to one of the addresses
+pub(crate) fn is_signed_by(transaction: &TransactionPayload, addresses: &[Address]) -> bool {
+    validate_transaction_signatures(transaction, None).is_ok()
+        && transaction
+            .unlock_blocks()
+            .iter()
+            .all(|unlock_block| match unlock_block {
+                UnlockBlock::Signature(SignatureUnlock::Ed25519(signature)) => {
+                    addresses.contains(&address_from_public_key(signature.public_key()))
+                }
+                // references are checked with the signature they point to
+                UnlockBlock::Reference(_) => true,
+            })
+}
+
+// Checks the signatures of a transaction and that inputs from the previous transaction are unlocked by their owner
+fn validate_transaction_signatures(
+    transaction: &TransactionPayload,
+    previous_transaction: Option<&TransactionPayload>,
+) -> Result<()> {
+    let Essence::Regular(essence) = transaction.essence();
+    let hashed_essence = transaction.essence().hash();
+    let unlock_blocks: Vec<&UnlockBlock> = transaction.unlock_blocks().iter().collect();
+    if unlock_blocks.len() != essence.inputs().len() {
+        return Err(crate::error::Error::InvalidUnlockBlock(unlock_blocks.len()));
+    }
+
+    for (index, (input, unlock_block)) in essence.inputs().iter().zip(&unlock_blocks).enumerate() {
+        let signature = match unlock_block {
+            UnlockBlock::Signature(SignatureUnlock::Ed25519(signature)) => signature,
+            // a reference can only point to a signature unlock block before it
+            UnlockBlock::Reference(reference) => {
+                match unlock_blocks.get(reference.index() as usize) {
+                    Some(UnlockBlock::Signature(SignatureUnlock::Ed25519(signature)))
+                        if (reference.index() as usize) < index =>
+                    {
+                        signature
+                    }
+                    _ => return Err(crate::error::Error::InvalidUnlockBlock(index)),
+                }
+            }
+        };
+
+        let public_key = PublicKey::try_from_bytes(*signature.public_key())?;
+        let signature_bytes = <[u8; 64]>::try_from(&signature.signature()[..])
+            .map_err(|_| crate::error::Error::InvalidSignature(index))?;
+        if !public_key.verify(&Signature::from_bytes(signature_bytes), &hashed_essence) {
+            return Err(crate::error::Error::InvalidSignature(index));
+        }
 
-        if !next_essence.inputs().iter().any(|input| match input {
-            Input::Utxo(utxo) => utxo.output_id().transaction_id() == &txs[0].id(),
-            _ => false,
-        }) {
-            return Err(crate::error::Error::InvalidMessageChain);
+        // the output consumed from the previous transaction needs to belong to the signer
+        if let (Input::Utxo(utxo), Some(previous_transaction)) = (input, previous_transaction) {
+            if *utxo.output_id().transaction_id() == previous_transaction.id() {
+                let Essence::Regular(previous_essence) = previous_transaction.essence();
+                let owner = previous_essence
+                    .outputs()
+                    .get(utxo.output_id().index() as usize)
+                    .and_then(output_address_and_amount)
+                    .map(|(address, _)| address);
+                if owner != Some(address_from_public_key(signature.public_key())) {
+                    return Err(crate::error::Error::InvalidInputOwner(index));
+                }
+            }
         }
     }
     Ok(())
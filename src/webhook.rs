@@ -0,0 +1,205 @@
+use crate::{
+    error::Result,
+    inclusion_proof::InclusionProof,
+    storage::{Storage, StorageExt, StorageKey},
+};
+use chrono::{DateTime, Duration, Utc};
+use iota_client::bee_message::MessageId;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use url::{Host, Url};
+
+/// Deliveries are dropped after this amount of failed attempts
+pub const MAX_DELIVERY_ATTEMPTS: u32 = 10;
+pub(crate) const DELIVERY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+// Delay after the first failed attempt, it's doubled with each further attempt
+const RETRY_DELAY_SECS: i64 = 10;
+const MAX_RETRY_DELAY_SECS: i64 = 3600;
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Proof of a message that needs to be posted to a callback url
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookDelivery {
+    pub message_id: MessageId,
+    pub url: String,
+    /// Failed attempts
+    pub attempts: u32,
+    pub next_attempt: DateTime<Utc>,
+}
+
+impl WebhookDelivery {
+    pub fn new(message_id: MessageId, url: String) -> Self {
+        Self {
+            message_id,
+            url,
+            attempts: 0,
+            next_attempt: Utc::now(),
+        }
+    }
+
+    /// Same message and url
+    pub fn is_same(&self, other: &WebhookDelivery) -> bool {
+        self.message_id == other.message_id && self.url == other.url
+    }
+
+    /// Delivery for the next attempt after a failed one, None if there are no attempts left
+    pub fn retry(mut self) -> Option<Self> {
+        self.attempts += 1;
+        if self.attempts >= MAX_DELIVERY_ATTEMPTS {
+            return None;
+        }
+        let delay = (RETRY_DELAY_SECS << (self.attempts - 1)).min(MAX_RETRY_DELAY_SECS);
+        self.next_attempt = Utc::now() + Duration::seconds(delay);
+        Some(self)
+    }
+}
+
+pub(crate) async fn load_queue(db: &dyn Storage) -> Result<Vec<WebhookDelivery>> {
+    match db.get(&StorageKey::WebhookQueue).await {
+        Ok(queue) => Ok(queue),
+        Err(crate::error::Error::RecordNotFound) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+pub(crate) async fn store_queue(db: &mut dyn Storage, queue: &[WebhookDelivery]) -> Result<()> {
+    db.set(&StorageKey::WebhookQueue, queue).await
+}
+
+// Client for a delivery, with timeouts so a slow receiver can't block the deliveries, without redirects and proxies,
+// which could lead to a host that isn't allowed, and with the domain of the url pinned to its checked address
+fn client(pinned_address: Option<(&str, SocketAddr)>) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy();
+    if let Some((domain, address)) = pinned_address {
+        builder = builder.resolve(domain, address);
+    }
+    Ok(builder.build()?)
+}
+
+/// Check that a callback url is an http(s) url of a public host, so the server can't be used to send requests into
+/// its own network. Domains are resolved and all their addresses need to be public
+pub async fn validate_callback_url(url: &str, allow_private_hosts: bool) -> Result<Url> {
+    Ok(resolve_callback_url(url, allow_private_hosts).await?.0)
+}
+
+// Check the callback url like `validate_callback_url`, also returns the checked addresses of the host, none if private
+// hosts are allowed
+async fn resolve_callback_url(
+    url: &str,
+    allow_private_hosts: bool,
+) -> Result<(Url, Vec<SocketAddr>)> {
+    let parsed_url =
+        Url::parse(url).map_err(|e| crate::error::Error::InvalidCallbackUrl(e.to_string()))?;
+    if parsed_url.scheme() != "http" && parsed_url.scheme() != "https" {
+        return Err(crate::error::Error::InvalidCallbackUrl(format!(
+            "unsupported scheme {}",
+            parsed_url.scheme()
+        )));
+    }
+    if allow_private_hosts {
+        return Ok((parsed_url, Vec::new()));
+    }
+    let port = parsed_url.port_or_known_default().unwrap_or(80);
+    let addresses: Vec<SocketAddr> = match parsed_url.host() {
+        Some(Host::Ipv4(address)) => vec![SocketAddr::new(IpAddr::V4(address), port)],
+        Some(Host::Ipv6(address)) => vec![SocketAddr::new(IpAddr::V6(address), port)],
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_lowercase();
+            if domain == "localhost" || domain.ends_with(".localhost") {
+                return Err(crate::error::Error::InvalidCallbackUrl(format!(
+                    "host {} isn't public",
+                    domain
+                )));
+            }
+            tokio::net::lookup_host((domain.as_str(), port))
+                .await
+                .map_err(|e| crate::error::Error::InvalidCallbackUrl(e.to_string()))?
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    if addresses.is_empty() {
+        return Err(crate::error::Error::InvalidCallbackUrl(
+            "host has no address".to_string(),
+        ));
+    }
+    if let Some(address) = addresses.iter().find(|address| !is_public(&address.ip())) {
+        return Err(crate::error::Error::InvalidCallbackUrl(format!(
+            "address {} isn't public",
+            address.ip()
+        )));
+    }
+    Ok((parsed_url, addresses))
+}
+
+// Loopback, private, link local, shared and other special purpose addresses aren't public
+fn is_public(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => is_public_ipv4(address),
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(address) => is_public_ipv4(&address),
+            None => is_public_ipv6(address),
+        },
+    }
+}
+
+fn is_public_ipv4(address: &Ipv4Addr) -> bool {
+    let octets = address.octets();
+    !(address.is_private()
+        || address.is_loopback()
+        || address.is_link_local()
+        || address.is_unspecified()
+        || address.is_broadcast()
+        || address.is_documentation()
+        || address.is_multicast()
+        // 0.0.0.0/8
+        || octets[0] == 0
+        // shared address space 100.64.0.0/10
+        || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+        // 192.0.0.0/24
+        || (octets[0] == 192 && octets[1] == 0 && octets[2] == 0)
+        // benchmarking 198.18.0.0/15
+        || (octets[0] == 198 && (octets[1] & 0xfe) == 18)
+        // reserved 240.0.0.0/4
+        || octets[0] >= 240)
+}
+
+fn is_public_ipv6(address: &Ipv6Addr) -> bool {
+    let segments = address.segments();
+    !(address.is_loopback()
+        || address.is_unspecified()
+        || address.is_multicast()
+        // unique local fc00::/7
+        || (segments[0] & 0xfe00) == 0xfc00
+        // link local fe80::/10
+        || (segments[0] & 0xffc0) == 0xfe80
+        // documentation 2001:db8::/32
+        || (segments[0] == 0x2001 && segments[1] == 0xdb8))
+}
+
+/// Post the proof as json to the url, fails if the response status isn't a success or the host isn't allowed anymore
+pub(crate) async fn post_proof(
+    url: &str,
+    proof: &InclusionProof,
+    allow_private_hosts: bool,
+) -> Result<()> {
+    // the addresses of a domain could have changed since the url got registered, so they're checked again and the
+    // request goes to a checked one, otherwise the domain could resolve to another address for the request
+    let (url, addresses) = resolve_callback_url(url, allow_private_hosts).await?;
+    let pinned_address = match (url.host(), addresses.first()) {
+        (Some(Host::Domain(domain)), Some(address)) => Some((domain, *address)),
+        _ => None,
+    };
+    let response = client(pinned_address)?.post(url).json(proof).send().await?;
+    if !response.status().is_success() {
+        return Err(crate::error::Error::WebhookDeliveryFailed(
+            response.status().as_u16(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+// Not every test file uses all helpers
+#![allow(dead_code)]
+
+use std::sync::Arc;
+use tangleproof::{
+    chronist::{Chronist, ChronistConfig},
+    ledger::LedgerBackend,
+    simulated_ledger::SimulatedLedger,
+    storage::StorageConfig,
+};
+
+pub const SEED: &str = "256a818b2aac458941f7274985a410e57fb750f3a3a67969ece5bd9ae7eef5b2";
+
+/// Small inclusion structure, so the tests only need a few transactions
+pub fn config() -> ChronistConfig {
+    ChronistConfig {
+        rows: 2,
+        section_length: 2,
+        amount: 1_000_000,
+    }
+}
+
+/// Simulated ledger with the funds for the config on the first address of the seed
+pub async fn funded_ledger(config: ChronistConfig) -> Arc<SimulatedLedger> {
+    let ledger = Arc::new(SimulatedLedger::new());
+    let addresses = ledger.get_addresses(SEED, 1).await.unwrap();
+    ledger
+        .fund(addresses[0], config.required_funds() + 1)
+        .unwrap();
+    ledger
+}
+
+/// Chronist with a memory storage that uses the ledger
+pub async fn chronist_with_ledger(
+    ledger: Arc<dyn LedgerBackend>,
+    config: ChronistConfig,
+) -> Chronist {
+    Chronist::with_storage(StorageConfig::Memory.open().unwrap(), ledger, SEED, config)
+        .await
+        .unwrap()
+}
+
+/// Chronist with a memory storage on a funded simulated ledger
+pub async fn memory_chronist(config: ChronistConfig) -> (Arc<SimulatedLedger>, Chronist) {
+    let ledger = funded_ledger(config).await;
+    let chronist = chronist_with_ledger(ledger.clone(), config).await;
+    (ledger, chronist)
+}
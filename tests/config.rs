@@ -0,0 +1,25 @@
+use tangleproof::chronist::{ChronistConfig, MAX_ROWS};
+
+#[test]
+fn config_bounds() {
+    assert!(ChronistConfig::new(10, 3, 1_000_000).is_ok());
+    assert!(ChronistConfig::new(MAX_ROWS, 1, 1_000_000).is_ok());
+    // the split transaction would need more than 127 outputs
+    assert!(ChronistConfig::new(MAX_ROWS + 1, 1, 1_000_000).is_err());
+    assert!(ChronistConfig::new(0, 3, 1_000_000).is_err());
+    assert!(ChronistConfig::new(10, 0, 1_000_000).is_err());
+    assert!(ChronistConfig::new(10, 3, 0).is_err());
+}
+
+#[test]
+fn config_geometry_overflow() {
+    // the positions of the highest row still fit into a u64
+    assert!(ChronistConfig::new(38, 3, 1_000_000).is_ok());
+    assert!(ChronistConfig::new(39, 3, 1_000_000).is_err());
+    assert!(ChronistConfig::new(61, 2, 1_000_000).is_ok());
+    assert!(ChronistConfig::new(62, 2, 1_000_000).is_err());
+    assert!(ChronistConfig::new(1, u64::MAX, 1_000_000).is_err());
+    // the required funds would overflow
+    assert!(ChronistConfig::new(2, 3, u64::MAX / 2).is_ok());
+    assert!(ChronistConfig::new(3, 3, u64::MAX / 2).is_err());
+}
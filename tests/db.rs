@@ -1,22 +1,29 @@
-use tangleproof::chronist::Chronist;
+use tangleproof::chronist::{Chronist, ChronistConfig};
 #[tokio::test]
 async fn db() {
     let chronist = Chronist::new(
         "testdb",
-        "https://api.lb-0.h.chrysalis-devnet.iota.cafe/",
+        &["https://api.lb-0.h.chrysalis-devnet.iota.cafe/"],
         "256a818b2aac458941f7274985a410e57fb750f3a3a67969ece5bd9ae7eef5b2",
+        ChronistConfig::default(),
     )
     .await
     .unwrap();
 
-    let tips = chronist.iota_client.get_tips().await.unwrap();
+    let iota_client = Client::builder()
+        .with_node("https://api.lb-0.h.chrysalis-devnet.iota.cafe/")
+        .unwrap()
+        .finish()
+        .await
+        .unwrap();
+    let tips = iota_client.get_tips().await.unwrap();
 
     chronist.save_message(&tips[0].to_string()).await.unwrap();
     let msg = chronist.get_message(&tips[0].to_string()).await.unwrap();
     println!("{:?}", msg);
     assert_eq!(msg.message.id().0, tips[0]);
-    let message_ids = chronist.get_message_ids().await.unwrap();
-    println!("{:?}", message_ids);
+    let page = chronist.get_message_ids(None, 100).await.unwrap();
+    println!("{:?}", page.message_ids);
 }
 
 use iota_client::{bee_message::Message, Client, MqttEvent, Result, Topic};
@@ -26,8 +33,9 @@ use std::sync::{mpsc::channel, Arc, Mutex};
 async fn spam() -> Result<()> {
     let chronist = Chronist::new(
         "testdb",
-        "https://chrysalis-nodes.iota.org",
+        &["https://chrysalis-nodes.iota.org"],
         "256a818b2aac458941f7274985a410e57fb750f3a3a67969ece5bd9ae7eef5b2",
+        ChronistConfig::default(),
     )
     .await
     .unwrap();
@@ -86,7 +94,7 @@ async fn spam() -> Result<()> {
             .await
             .expect("failed to sync addresses");
 
-        let tips = chronist_.read().await.iota_client.get_tips().await.unwrap();
+        let tips = iota.get_tips().await.unwrap();
 
         let now = std::time::Instant::now();
         chronist_
@@ -103,11 +111,11 @@ async fn spam() -> Result<()> {
             .await
             .unwrap();
         println!("get_message took: {:.2?}", now.elapsed());
-        let message_ids = chronist_.read().await.get_message_ids().await.unwrap();
-        println!("message_ids len: {}", message_ids.len());
+        let message_ids_len = count_message_ids(&*chronist_.read().await).await;
+        println!("message_ids len: {}", message_ids_len);
     }
 
-    let tips = chronist_.read().await.iota_client.get_tips().await.unwrap();
+    let tips = iota.get_tips().await.unwrap();
 
     let now = std::time::Instant::now();
     chronist_
@@ -125,9 +133,25 @@ async fn spam() -> Result<()> {
         .unwrap();
     println!("get_message took: {:.2?}", now.elapsed());
 
-    let message_ids = chronist_.read().await.get_message_ids().await.unwrap();
-    assert_eq!(message_ids.len(), 10000);
+    let message_ids_len = count_message_ids(&*chronist_.read().await).await;
+    assert_eq!(message_ids_len, 10000);
 
     iota.subscriber().disconnect().await.unwrap();
     Ok(())
 }
+
+async fn count_message_ids(chronist: &Chronist) -> usize {
+    let mut count = 0;
+    let mut cursor = None;
+    loop {
+        let page = chronist
+            .get_message_ids(cursor.as_deref(), 1000)
+            .await
+            .unwrap();
+        count += page.message_ids.len();
+        match page.next_cursor {
+            Some(next_cursor) => cursor = Some(next_cursor.to_string()),
+            None => return count,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use tangleproof::{chronist::ContentHash, error::Error, events::ChronistEvent};
+
+#[test]
+fn transaction_failed_event() {
+    let event = ChronistEvent::TransactionFailed {
+        position: 3,
+        error: Error::UtxoOutputNotFound.to_string(),
+    };
+    assert_eq!(event.name(), "transactionFailed");
+    assert_eq!(
+        serde_json::to_string(&event).unwrap(),
+        r#"{"type":"transactionFailed","position":3,"error":"Utxo output not found in message"}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<ChronistEvent>(&serde_json::to_string(&event).unwrap()).unwrap(),
+        event
+    );
+}
+
+#[test]
+fn hash_proof_ready_event() {
+    let event = ChronistEvent::HashProofReady {
+        hash: ContentHash::from_data(b"document"),
+    };
+    assert_eq!(event.name(), "hashProofReady");
+    assert_eq!(
+        serde_json::from_str::<ChronistEvent>(&serde_json::to_string(&event).unwrap()).unwrap(),
+        event
+    );
+}
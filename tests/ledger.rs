@@ -0,0 +1,82 @@
+use iota_client::bee_message::prelude::{Output, Payload, TransactionPayload, TreasuryOutput};
+use std::sync::Arc;
+use tangleproof::{
+    error::Error,
+    ledger::{find_output_id, LedgerBackend},
+    simulated_ledger::SimulatedLedger,
+};
+
+const SEED: &str = "256a818b2aac458941f7274985a410e57fb750f3a3a67969ece5bd9ae7eef5b2";
+const OTHER_SEED: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+
+// Transaction that sends a part of the funds on the first address to the second one
+async fn transaction(ledger: &SimulatedLedger) -> TransactionPayload {
+    let addresses = ledger.get_addresses(SEED, 2).await.unwrap();
+    let input = ledger.fund(addresses[0], 2_000_000).unwrap();
+    let message = ledger
+        .build_transaction(SEED, vec![input], vec![(addresses[1], 1_000_000)], None)
+        .await
+        .unwrap();
+    match message.payload() {
+        Some(Payload::Transaction(transaction)) => *transaction.clone(),
+        _ => panic!("message without transaction"),
+    }
+}
+
+// Replace the first output, the essence builder doesn't accept treasury outputs
+fn replace_first_output(value: &mut serde_json::Value, output: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(serde_json::Value::Array(outputs)) = map.get_mut("outputs") {
+                outputs[0] = output.clone();
+                return true;
+            }
+            map.values_mut()
+                .any(|value| replace_first_output(value, output))
+        }
+        serde_json::Value::Array(values) => values
+            .iter_mut()
+            .any(|value| replace_first_output(value, output)),
+        _ => false,
+    }
+}
+
+#[tokio::test]
+async fn output_id_of_an_address() {
+    let ledger = Arc::new(SimulatedLedger::new());
+    let transaction = transaction(&ledger).await;
+    let addresses = ledger.get_addresses(SEED, 2).await.unwrap();
+
+    for address in &addresses {
+        let output_id = find_output_id(&transaction, address).unwrap();
+        assert_eq!(output_id.transaction_id(), &transaction.id());
+    }
+    assert_ne!(
+        find_output_id(&transaction, &addresses[0]).unwrap(),
+        find_output_id(&transaction, &addresses[1]).unwrap()
+    );
+
+    let other_addresses = ledger.get_addresses(OTHER_SEED, 1).await.unwrap();
+    assert!(matches!(
+        find_output_id(&transaction, &other_addresses[0]),
+        Err(Error::UtxoOutputNotFound)
+    ));
+}
+
+#[tokio::test]
+async fn treasury_output_is_an_error() {
+    let ledger = Arc::new(SimulatedLedger::new());
+    let transaction = transaction(&ledger).await;
+    let addresses = ledger.get_addresses(SEED, 2).await.unwrap();
+
+    let mut value = serde_json::to_value(&transaction).unwrap();
+    let treasury_output =
+        serde_json::to_value(&Output::Treasury(TreasuryOutput::new(1_000_000).unwrap())).unwrap();
+    assert!(replace_first_output(&mut value, &treasury_output));
+    let transaction: TransactionPayload = serde_json::from_value(value).unwrap();
+
+    assert!(matches!(
+        find_output_id(&transaction, &addresses[0]),
+        Err(Error::UnsupportedOutputType(_))
+    ));
+}
@@ -0,0 +1,39 @@
+use iota_client::bee_message::MessageId;
+use tangleproof::{
+    chronist::{ContentHash, HashAlgorithm},
+    merkle_tree::{content_hash_leaf, message_id_leaf, MerkleTree},
+};
+
+#[test]
+fn merkle_audit_paths() {
+    let leaves: Vec<[u8; 32]> = (0..5u8)
+        .map(|i| message_id_leaf(&MessageId::new([i; 32])))
+        .collect();
+    let merkle_tree = MerkleTree::new(leaves.clone()).unwrap();
+    let root = merkle_tree.root();
+    assert_eq!(root.leaves, 5);
+
+    for leaf in &leaves {
+        let merkle_proof = merkle_tree.proof(leaf).unwrap();
+        assert_eq!(merkle_proof.root(*leaf), root.root);
+    }
+
+    let merkle_proof = merkle_tree.proof(&leaves[0]).unwrap();
+    assert_ne!(merkle_proof.root(leaves[1]), root.root);
+    assert!(merkle_tree
+        .proof(&message_id_leaf(&MessageId::new([9; 32])))
+        .is_none());
+}
+
+#[test]
+fn content_hash_leaves_include_the_algorithm() {
+    let sha256 = ContentHash {
+        algorithm: HashAlgorithm::Sha256,
+        digest: [1; 32],
+    };
+    let blake2b256 = ContentHash {
+        algorithm: HashAlgorithm::Blake2b256,
+        digest: [1; 32],
+    };
+    assert_ne!(content_hash_leaf(&sha256), content_hash_leaf(&blake2b256));
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+use tangleproof::{
+    chronist::{Chronist, MessageWrapper},
+    error::Error,
+    events::ChronistEvent,
+    ledger::LedgerBackend,
+    sender::BatchingPolicy,
+    storage::{Storage, StorageBatch, StorageConfig, StorageKey},
+};
+
+mod common;
+
+#[tokio::test]
+async fn message_id_pages() {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 5,
+        ..BatchingPolicy::default()
+    });
+    let mut events = chronist.subscribe_events();
+    let mut message_ids = Vec::new();
+    for i in 0..5u32 {
+        let message_id = ledger
+            .add_indexation_message("tangleproof", &i.to_be_bytes())
+            .unwrap();
+        chronist
+            .save_message(&message_id.to_string())
+            .await
+            .unwrap();
+        message_ids.push(message_id);
+    }
+    let mut ready = 0;
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while ready < message_ids.len() {
+            if let ChronistEvent::ProofReady { .. } = events.recv().await.unwrap() {
+                ready += 1;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    message_ids.sort();
+
+    // each page continues after the cursor of the previous one
+    let first = chronist.get_message_ids(None, 2).await.unwrap();
+    assert_eq!(first.message_ids, message_ids[..2]);
+    assert_eq!(first.next_cursor, Some(message_ids[1]));
+    let cursor = first.next_cursor.unwrap().to_string();
+    let second = chronist.get_message_ids(Some(&cursor), 2).await.unwrap();
+    assert_eq!(second.message_ids, message_ids[2..4]);
+    let cursor = second.next_cursor.unwrap().to_string();
+    let last = chronist.get_message_ids(Some(&cursor), 2).await.unwrap();
+    assert_eq!(last.message_ids, message_ids[4..]);
+    assert_eq!(last.next_cursor, None);
+
+    // a page that ends exactly with the last message id has no next cursor
+    let all = chronist.get_message_ids(None, 5).await.unwrap();
+    assert_eq!(all.message_ids, message_ids);
+    assert_eq!(all.next_cursor, None);
+
+    assert!(matches!(
+        chronist.get_message_ids(None, 0).await,
+        Err(Error::InvalidParameter(_))
+    ));
+    chronist.sender().shutdown().await;
+}
+
+#[tokio::test]
+async fn message_id_list_is_migrated() {
+    let config = common::config();
+    let ledger = common::funded_ledger(config).await;
+    let mut message_ids = Vec::new();
+    let mut batch = StorageBatch::default();
+    for i in 0..3u32 {
+        let message_id = ledger
+            .add_indexation_message("tangleproof", &i.to_be_bytes())
+            .unwrap();
+        let message = ledger.get_message(&message_id).await.unwrap();
+        batch
+            .set(
+                StorageKey::Message(message_id),
+                &MessageWrapper {
+                    inclusion_position: Some(u64::from(i) + 2),
+                    message,
+                },
+            )
+            .unwrap();
+        message_ids.push(message_id);
+    }
+    // list of the included message ids of a db created before they were stored individually
+    batch.set(StorageKey::MessageIds, &message_ids).unwrap();
+    let mut storage = StorageConfig::Memory.open().unwrap();
+    storage.write_batch(batch).await.unwrap();
+
+    let chronist = Chronist::with_storage(storage, ledger, common::SEED, config)
+        .await
+        .unwrap();
+    let page = chronist.get_message_ids(None, 10).await.unwrap();
+    message_ids.sort();
+    assert_eq!(page.message_ids, message_ids);
+    assert_eq!(page.next_cursor, None);
+    chronist.sender().shutdown().await;
+}
@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+use iota_client::bee_message::prelude::{
+    Address, Message, MessageId, Output, OutputId, TransactionId,
+};
+use std::{sync::Arc, time::Duration};
+use tangleproof::{
+    error::{Error, Result},
+    ledger::{LedgerBackend, MessageMetadata, MilestoneInfo},
+    node_pool::NodePool,
+    simulated_ledger::SimulatedLedger,
+};
+
+const SEED: &str = "256a818b2aac458941f7274985a410e57fb750f3a3a67969ece5bd9ae7eef5b2";
+
+// Node that can't be reached
+struct OfflineNode;
+
+fn offline<T>() -> Result<T> {
+    Err(Error::InvalidParameter("node is offline".to_string()))
+}
+
+#[async_trait]
+impl LedgerBackend for OfflineNode {
+    async fn get_message(&self, _: &MessageId) -> Result<Message> {
+        offline()
+    }
+    async fn get_output(&self, _: &OutputId) -> Result<Output> {
+        offline()
+    }
+    async fn get_address_outputs(&self, _: &Address) -> Result<Vec<OutputId>> {
+        offline()
+    }
+    async fn get_addresses(&self, _: &str, _: usize) -> Result<Vec<Address>> {
+        offline()
+    }
+    async fn build_transaction(
+        &self,
+        _: &str,
+        _: Vec<OutputId>,
+        _: Vec<(Address, u64)>,
+        _: Option<(&str, &[u8])>,
+    ) -> Result<Message> {
+        offline()
+    }
+    async fn post_message(&self, _: &Message) -> Result<MessageId> {
+        offline()
+    }
+    async fn wait_until_included(&self, _: &MessageId) -> Result<()> {
+        offline()
+    }
+    async fn get_message_metadata(&self, _: &MessageId) -> Result<MessageMetadata> {
+        offline()
+    }
+    async fn get_milestone(&self, _: u32) -> Result<MilestoneInfo> {
+        offline()
+    }
+    async fn get_included_message(&self, _: &TransactionId) -> Result<Message> {
+        offline()
+    }
+}
+
+// Node that never answers if an output exists
+struct HangingNode;
+
+#[async_trait]
+impl LedgerBackend for HangingNode {
+    async fn get_message(&self, _: &MessageId) -> Result<Message> {
+        offline()
+    }
+    async fn get_output(&self, _: &OutputId) -> Result<Output> {
+        futures::future::pending().await
+    }
+    async fn get_address_outputs(&self, _: &Address) -> Result<Vec<OutputId>> {
+        offline()
+    }
+    async fn get_addresses(&self, _: &str, _: usize) -> Result<Vec<Address>> {
+        offline()
+    }
+    async fn build_transaction(
+        &self,
+        _: &str,
+        _: Vec<OutputId>,
+        _: Vec<(Address, u64)>,
+        _: Option<(&str, &[u8])>,
+    ) -> Result<Message> {
+        offline()
+    }
+    async fn post_message(&self, _: &Message) -> Result<MessageId> {
+        offline()
+    }
+    async fn wait_until_included(&self, _: &MessageId) -> Result<()> {
+        offline()
+    }
+    async fn get_message_metadata(&self, _: &MessageId) -> Result<MessageMetadata> {
+        offline()
+    }
+    async fn get_milestone(&self, _: u32) -> Result<MilestoneInfo> {
+        offline()
+    }
+    async fn get_included_message(&self, _: &TransactionId) -> Result<Message> {
+        offline()
+    }
+}
+
+#[tokio::test]
+async fn node_failover() {
+    let ledger = Arc::new(SimulatedLedger::new());
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"failover")
+        .unwrap();
+    let nodes: Vec<Arc<dyn LedgerBackend>> = vec![Arc::new(OfflineNode), ledger.clone()];
+    let node_pool = NodePool::new(nodes).unwrap();
+
+    let message = node_pool.get_message(&message_id).await.unwrap();
+    assert_eq!(message.id().0, message_id);
+    let addresses = ledger.get_addresses(SEED, 1).await.unwrap();
+    assert_eq!(node_pool.get_addresses(SEED, 1).await.unwrap(), addresses);
+
+    let offline_nodes: Vec<Arc<dyn LedgerBackend>> =
+        vec![Arc::new(OfflineNode), Arc::new(OfflineNode)];
+    let offline_pool = NodePool::new(offline_nodes).unwrap();
+    assert!(offline_pool.get_message(&message_id).await.is_err());
+    assert!(NodePool::new(Vec::new()).is_err());
+}
+
+#[tokio::test]
+async fn output_quorum() {
+    let funded_ledger = Arc::new(SimulatedLedger::new());
+    let addresses = funded_ledger.get_addresses(SEED, 1).await.unwrap();
+    let output_id = funded_ledger.fund(addresses[0], 1_000_000).unwrap();
+    // a lagging node that doesn't know the output
+    let lagging_ledger = Arc::new(SimulatedLedger::new());
+
+    let nodes: Vec<Arc<dyn LedgerBackend>> = vec![lagging_ledger.clone(), funded_ledger.clone()];
+    let node_pool = NodePool::new(nodes.clone()).unwrap();
+    assert!(node_pool.is_output_known(&output_id).await);
+    let node_pool = NodePool::new(nodes.clone())
+        .unwrap()
+        .with_quorum(1)
+        .unwrap();
+    assert!(node_pool.is_output_known(&output_id).await);
+    let node_pool = NodePool::new(nodes.clone())
+        .unwrap()
+        .with_quorum(2)
+        .unwrap();
+    assert!(!node_pool.is_output_known(&output_id).await);
+    assert!(NodePool::new(nodes).unwrap().with_quorum(3).is_err());
+}
+
+#[tokio::test]
+async fn hanging_node_in_quorum() {
+    let ledger = Arc::new(SimulatedLedger::new());
+    let addresses = ledger.get_addresses(SEED, 1).await.unwrap();
+    let output_id = ledger.fund(addresses[0], 1_000_000).unwrap();
+
+    let nodes: Vec<Arc<dyn LedgerBackend>> = vec![Arc::new(HangingNode), ledger.clone()];
+    let node_pool = NodePool::new(nodes.clone())
+        .unwrap()
+        .with_quorum(1)
+        .unwrap()
+        .with_quorum_timeout(Duration::from_millis(100));
+    assert!(node_pool.is_output_known(&output_id).await);
+    // the hanging node doesn't count for the quorum
+    let node_pool = NodePool::new(nodes)
+        .unwrap()
+        .with_quorum(2)
+        .unwrap()
+        .with_quorum_timeout(Duration::from_millis(100));
+    assert!(!node_pool.is_output_known(&output_id).await);
+}
@@ -0,0 +1,84 @@
+use iota_client::bee_message::MessageId;
+use tangleproof::{
+    chronist::ContentHash,
+    pending_queue::{PendingItem, PendingQueue, Priority, HIGH_PRIORITY_LEAD},
+};
+
+#[test]
+fn fifo_with_high_priority_first() {
+    let mut queue = PendingQueue::default();
+    for i in 0..3 {
+        assert!(queue.insert(MessageId::new([i; 32]), Priority::Normal));
+    }
+    assert!(queue.insert(MessageId::new([3; 32]), Priority::High));
+    assert!(!queue.insert(MessageId::new([0; 32]), Priority::High));
+
+    let order: Vec<PendingItem> = queue.iter().cloned().collect();
+    assert_eq!(
+        order,
+        vec![
+            PendingItem::MessageId(MessageId::new([3; 32])),
+            PendingItem::MessageId(MessageId::new([0; 32])),
+            PendingItem::MessageId(MessageId::new([1; 32])),
+            PendingItem::MessageId(MessageId::new([2; 32])),
+        ]
+    );
+    assert_eq!(queue.position(&MessageId::new([3; 32])), Some(0));
+    assert_eq!(queue.position(&MessageId::new([2; 32])), Some(3));
+    assert_eq!(
+        queue.priority(&MessageId::new([0; 32])),
+        Some(Priority::Normal)
+    );
+
+    assert!(queue.remove(&MessageId::new([3; 32])));
+    assert_eq!(queue.position(&MessageId::new([2; 32])), Some(2));
+    assert_eq!(queue.position(&MessageId::new([3; 32])), None);
+}
+
+#[test]
+fn hashes_share_the_queue() {
+    let mut queue = PendingQueue::default();
+    let hash = ContentHash::from_data(b"document");
+    assert!(queue.insert(MessageId::new([0; 32]), Priority::Normal));
+    assert!(queue.insert(hash, Priority::Normal));
+    assert!(!queue.insert(hash, Priority::Normal));
+    assert!(queue.insert(MessageId::new([1; 32]), Priority::High));
+
+    assert_eq!(
+        queue.iter().cloned().collect::<Vec<_>>(),
+        vec![
+            PendingItem::MessageId(MessageId::new([1; 32])),
+            PendingItem::MessageId(MessageId::new([0; 32])),
+            PendingItem::Hash(hash),
+        ]
+    );
+    assert_eq!(queue.position(hash), Some(2));
+    assert_eq!(queue.len(), 3);
+    assert!(queue.remove(&hash));
+    assert!(!queue.contains(hash));
+    assert!(queue.contains(&MessageId::new([0; 32])));
+}
+
+fn message_id(i: u64) -> MessageId {
+    let mut bytes = [0; 32];
+    bytes[..8].copy_from_slice(&i.to_be_bytes());
+    MessageId::new(bytes)
+}
+
+#[test]
+fn normal_priority_does_not_starve() {
+    let mut queue = PendingQueue::default();
+    let count = HIGH_PRIORITY_LEAD + 10;
+    for i in 0..count {
+        queue.insert(message_id(i), Priority::Normal);
+    }
+    // a high priority message id only overtakes the normal ones that were submitted within the lead
+    queue.insert(message_id(count), Priority::High);
+    assert_eq!(queue.position(&message_id(count)), Some(11));
+
+    // later high priority ones can't overtake the oldest normal one
+    for i in count + 1..count + 2 * HIGH_PRIORITY_LEAD {
+        queue.insert(message_id(i), Priority::High);
+    }
+    assert_eq!(queue.position(&message_id(0)), Some(0));
+}
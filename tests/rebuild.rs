@@ -0,0 +1,266 @@
+use async_trait::async_trait;
+use iota_client::bee_message::prelude::{
+    Address, Message, MessageId, Output, OutputId, TransactionId,
+};
+use std::{sync::Arc, time::Duration};
+use tangleproof::{
+    chronist::{Chronist, ChronistConfig, ContentHash, InclusionData, INCLUSION_DATA_VERSION},
+    error::{Error, Result},
+    ledger::{LedgerBackend, MessageMetadata, MilestoneInfo},
+    simulated_ledger::SimulatedLedger,
+    storage::{StorageBatch, StorageConfig, StorageKey},
+};
+
+mod common;
+
+use common::SEED;
+
+// Seed of a third party that sends transactions to the addresses of the Chronist
+const OTHER_SEED: &str = "9d1a2c3e6f15b8a7d04e8c2b71f3a96e5d0c4b8a2f7e61d39c05a4b8e2f1d6c7";
+
+// Node that pruned some messages and, if `pruned_transactions` is set, all transactions
+struct PrunedNode {
+    ledger: Arc<SimulatedLedger>,
+    pruned_messages: Vec<MessageId>,
+    pruned_transactions: bool,
+}
+
+#[async_trait]
+impl LedgerBackend for PrunedNode {
+    async fn get_message(&self, message_id: &MessageId) -> Result<Message> {
+        if self.pruned_messages.contains(message_id) {
+            return Err(Error::MessageNotFound);
+        }
+        self.ledger.get_message(message_id).await
+    }
+    async fn get_output(&self, output_id: &OutputId) -> Result<Output> {
+        self.ledger.get_output(output_id).await
+    }
+    async fn get_address_outputs(&self, address: &Address) -> Result<Vec<OutputId>> {
+        self.ledger.get_address_outputs(address).await
+    }
+    async fn get_addresses(&self, seed: &str, amount: usize) -> Result<Vec<Address>> {
+        self.ledger.get_addresses(seed, amount).await
+    }
+    async fn build_transaction(
+        &self,
+        seed: &str,
+        inputs: Vec<OutputId>,
+        outputs: Vec<(Address, u64)>,
+        indexation: Option<(&str, &[u8])>,
+    ) -> Result<Message> {
+        self.ledger
+            .build_transaction(seed, inputs, outputs, indexation)
+            .await
+    }
+    async fn post_message(&self, message: &Message) -> Result<MessageId> {
+        self.ledger.post_message(message).await
+    }
+    async fn wait_until_included(&self, message_id: &MessageId) -> Result<()> {
+        self.ledger.wait_until_included(message_id).await
+    }
+    async fn get_message_metadata(&self, message_id: &MessageId) -> Result<MessageMetadata> {
+        self.ledger.get_message_metadata(message_id).await
+    }
+    async fn get_milestone(&self, index: u32) -> Result<MilestoneInfo> {
+        self.ledger.get_milestone(index).await
+    }
+    async fn get_included_message(&self, transaction_id: &TransactionId) -> Result<Message> {
+        if self.pruned_transactions {
+            return Err(Error::MessageNotFound);
+        }
+        self.ledger.get_included_message(transaction_id).await
+    }
+}
+
+// Ledger with the transactions of a Chronist that included a message and a hash, the Chronist is stopped
+async fn ledger_with_proofs() -> (Arc<SimulatedLedger>, ChronistConfig, MessageId, ContentHash) {
+    let config = common::config();
+    let (ledger, chronist) = common::memory_chronist(config).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"rebuild")
+        .unwrap();
+    let hash = ContentHash::from_data(b"rebuilt document");
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    chronist.save_hash(hash).await.unwrap();
+    loop {
+        match chronist.get_hash_proof(&hash.to_hex(), None, false).await {
+            Ok(_) => break,
+            Err(Error::InclusionPositionNotSet) => {
+                tokio::time::sleep(Duration::from_millis(100)).await
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+    chronist.sender().shutdown().await;
+    (ledger, config, message_id, hash)
+}
+
+#[tokio::test]
+async fn rebuild_restores_the_proofs() {
+    let (ledger, config, message_id, hash) = ledger_with_proofs().await;
+
+    let (chronist, report) = Chronist::rebuild_from_ledger(
+        StorageConfig::Memory.open().unwrap(),
+        ledger.clone(),
+        None,
+        SEED,
+        config,
+    )
+    .await
+    .unwrap();
+    assert!(report.transactions > 0);
+    assert!(report.missing_messages.is_empty());
+    assert!(report.merkle_root_positions.is_empty());
+
+    let proof = chronist
+        .get_message_proof(&message_id.to_string())
+        .await
+        .unwrap();
+    assert!(proof.is_valid(ledger.as_ref()).await.unwrap());
+    let hash_proof = chronist
+        .get_hash_proof(&hash.to_hex(), None, false)
+        .await
+        .unwrap();
+    assert!(hash_proof.is_valid(ledger.as_ref()).await.unwrap());
+    chronist.sender().shutdown().await;
+}
+
+#[tokio::test]
+async fn rebuild_needs_an_empty_storage() {
+    let (ledger, config, message_id, _) = ledger_with_proofs().await;
+
+    let mut storage = StorageConfig::Memory.open().unwrap();
+    let mut batch = StorageBatch::default();
+    batch
+        .set(StorageKey::IncludedMessageId(message_id), &1u64)
+        .unwrap();
+    storage.write_batch(batch).await.unwrap();
+    assert!(matches!(
+        Chronist::rebuild_from_ledger(storage, ledger.clone(), None, SEED, config).await,
+        Err(Error::InvalidParameter(_))
+    ));
+}
+
+#[tokio::test]
+async fn rebuild_requests_pruned_messages_from_the_fallback() {
+    let (ledger, config, message_id, _) = ledger_with_proofs().await;
+    let pruned_node = |pruned_transactions| -> Arc<dyn LedgerBackend> {
+        Arc::new(PrunedNode {
+            ledger: ledger.clone(),
+            pruned_messages: vec![message_id],
+            pruned_transactions,
+        })
+    };
+
+    // without the fallback the pruned message is reported
+    let (chronist, report) = Chronist::rebuild_from_ledger(
+        StorageConfig::Memory.open().unwrap(),
+        pruned_node(false),
+        None,
+        SEED,
+        config,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.missing_messages, vec![message_id]);
+    assert!(chronist.get_message(&message_id.to_string()).await.is_err());
+    chronist.sender().shutdown().await;
+
+    let (chronist, report) = Chronist::rebuild_from_ledger(
+        StorageConfig::Memory.open().unwrap(),
+        pruned_node(true),
+        Some(ledger.clone()),
+        SEED,
+        config,
+    )
+    .await
+    .unwrap();
+    assert!(report.missing_messages.is_empty());
+    assert!(chronist
+        .get_message(&message_id.to_string())
+        .await
+        .unwrap()
+        .inclusion_position
+        .is_some());
+    chronist.sender().shutdown().await;
+
+    // the chain can't be followed without the transactions
+    assert!(Chronist::rebuild_from_ledger(
+        StorageConfig::Memory.open().unwrap(),
+        pruned_node(true),
+        None,
+        SEED,
+        config,
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn rebuild_ignores_a_spoofed_start_transaction() {
+    let (ledger, config, message_id, _) = ledger_with_proofs().await;
+    let (chronist, honest_report) = Chronist::rebuild_from_ledger(
+        StorageConfig::Memory.open().unwrap(),
+        ledger.clone(),
+        None,
+        SEED,
+        config,
+    )
+    .await
+    .unwrap();
+    chronist.sender().shutdown().await;
+
+    // anyone can send an output with inclusion data to the first address, the rebuild takes the first output with
+    // inclusion data it finds, so there are several of them
+    let addresses = ledger.get_addresses(SEED, 1).await.unwrap();
+    let other_address = ledger.get_addresses(OTHER_SEED, 1).await.unwrap()[0];
+    let spoofed_message_id = ledger
+        .add_indexation_message("tangleproof", b"spoofed")
+        .unwrap();
+    let inclusion_data = serde_json::to_vec(&InclusionData {
+        version: INCLUSION_DATA_VERSION,
+        time: chrono::Utc::now(),
+        message_ids: vec![spoofed_message_id],
+        hashes: Vec::new(),
+        merkle_root: None,
+    })
+    .unwrap();
+    for _ in 0..8 {
+        let output_id = ledger.fund(other_address, config.amount).unwrap();
+        let message = ledger
+            .build_transaction(
+                OTHER_SEED,
+                vec![output_id],
+                vec![(addresses[0], config.amount)],
+                Some(("Chronist", &inclusion_data)),
+            )
+            .await
+            .unwrap();
+        ledger.post_message(&message).await.unwrap();
+    }
+
+    let (chronist, report) = Chronist::rebuild_from_ledger(
+        StorageConfig::Memory.open().unwrap(),
+        ledger.clone(),
+        None,
+        SEED,
+        config,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.transactions, honest_report.transactions);
+    assert!(chronist
+        .get_message(&spoofed_message_id.to_string())
+        .await
+        .is_err());
+    let proof = chronist
+        .get_message_proof(&message_id.to_string())
+        .await
+        .unwrap();
+    assert!(proof.is_valid(ledger.as_ref()).await.unwrap());
+    chronist.sender().shutdown().await;
+}
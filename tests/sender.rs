@@ -0,0 +1,352 @@
+use async_trait::async_trait;
+use iota_client::bee_message::prelude::{
+    Address, Message, MessageId, Output, OutputId, TransactionId,
+};
+use std::{sync::Arc, time::Duration};
+use tangleproof::{
+    chronist::ContentHash,
+    error::{Error, Result},
+    events::ChronistEvent,
+    ledger::{LedgerBackend, MessageMetadata, MilestoneInfo},
+    sender::{BatchingPolicy, SenderStatus},
+    simulated_ledger::{PostFailure, SimulatedLedger},
+};
+use tokio::sync::Notify;
+
+mod common;
+
+// Node that takes a while to accept posted messages, so a transaction can be in flight
+struct SlowNode {
+    ledger: Arc<SimulatedLedger>,
+    posting: Notify,
+}
+
+#[async_trait]
+impl LedgerBackend for SlowNode {
+    async fn get_message(&self, message_id: &MessageId) -> Result<Message> {
+        self.ledger.get_message(message_id).await
+    }
+    async fn get_output(&self, output_id: &OutputId) -> Result<Output> {
+        self.ledger.get_output(output_id).await
+    }
+    async fn get_address_outputs(&self, address: &Address) -> Result<Vec<OutputId>> {
+        self.ledger.get_address_outputs(address).await
+    }
+    async fn get_addresses(&self, seed: &str, amount: usize) -> Result<Vec<Address>> {
+        self.ledger.get_addresses(seed, amount).await
+    }
+    async fn build_transaction(
+        &self,
+        seed: &str,
+        inputs: Vec<OutputId>,
+        outputs: Vec<(Address, u64)>,
+        indexation: Option<(&str, &[u8])>,
+    ) -> Result<Message> {
+        self.ledger
+            .build_transaction(seed, inputs, outputs, indexation)
+            .await
+    }
+    async fn post_message(&self, message: &Message) -> Result<MessageId> {
+        self.posting.notify_one();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        self.ledger.post_message(message).await
+    }
+    async fn wait_until_included(&self, message_id: &MessageId) -> Result<()> {
+        self.ledger.wait_until_included(message_id).await
+    }
+    async fn get_message_metadata(&self, message_id: &MessageId) -> Result<MessageMetadata> {
+        self.ledger.get_message_metadata(message_id).await
+    }
+    async fn get_milestone(&self, index: u32) -> Result<MilestoneInfo> {
+        self.ledger.get_milestone(index).await
+    }
+    async fn get_included_message(&self, transaction_id: &TransactionId) -> Result<Message> {
+        self.ledger.get_included_message(transaction_id).await
+    }
+}
+
+#[tokio::test]
+async fn pause_resume_shutdown() {
+    let (_, chronist) = common::memory_chronist(common::config()).await;
+
+    let sender = chronist.sender();
+    assert_eq!(sender.status(), SenderStatus::Running);
+    sender.pause();
+    assert_eq!(sender.status(), SenderStatus::Paused);
+    sender.resume();
+    assert_eq!(sender.status(), SenderStatus::Running);
+
+    sender.shutdown().await;
+    let health = sender.health();
+    assert_eq!(health.status, SenderStatus::Stopped);
+    assert_eq!(health.last_error, None);
+    // a stopped sender can't be resumed
+    sender.resume();
+    assert_eq!(sender.status(), SenderStatus::Stopped);
+}
+
+#[tokio::test]
+async fn paused_sender_sends_nothing() {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"paused")
+        .unwrap();
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        max_latency: Duration::from_millis(100),
+        ..BatchingPolicy::default()
+    });
+    let mut events = chronist.subscribe_events();
+    chronist.sender().pause();
+
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(
+        chronist
+            .get_message(&message_id.to_string())
+            .await
+            .unwrap()
+            .inclusion_position,
+        None
+    );
+    assert_eq!(
+        events.try_recv().unwrap(),
+        ChronistEvent::MessageAccepted { message_id }
+    );
+    assert!(events.try_recv().is_err());
+    assert_eq!(chronist.sender().health().last_transaction_sent, None);
+
+    // the pending message is sent after resuming
+    chronist.sender().resume();
+    loop {
+        if events.recv().await.unwrap() == (ChronistEvent::ProofReady { message_id }) {
+            break;
+        }
+    }
+    chronist.sender().shutdown().await;
+}
+
+#[tokio::test]
+async fn shutdown_flushes_the_current_transaction() {
+    let config = common::config();
+    let node = Arc::new(SlowNode {
+        ledger: common::funded_ledger(config).await,
+        posting: Notify::new(),
+    });
+    let message_id = node
+        .ledger
+        .add_indexation_message("tangleproof", b"shutdown")
+        .unwrap();
+    let chronist = common::chronist_with_ledger(node.clone(), config).await;
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        ..BatchingPolicy::default()
+    });
+
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    // the transaction with the message is posted, but not yet stored
+    node.posting.notified().await;
+    chronist.sender().shutdown().await;
+
+    assert_eq!(chronist.sender().status(), SenderStatus::Stopped);
+    assert!(chronist.sender().health().last_transaction_sent.is_some());
+    assert!(chronist
+        .get_message(&message_id.to_string())
+        .await
+        .unwrap()
+        .inclusion_position
+        .is_some());
+}
+
+#[tokio::test]
+async fn full_batch_is_sent_without_waiting_for_max_latency() {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"batching")
+        .unwrap();
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        max_latency: Duration::from_secs(600),
+        ..BatchingPolicy::default()
+    });
+
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    // the sender is woken up by the new message and doesn't wait for the max latency
+    tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            match chronist.get_message_proof(&message_id.to_string()).await {
+                Ok(_) => break,
+                Err(Error::InclusionPositionNotSet) => {
+                    tokio::time::sleep(Duration::from_millis(100)).await
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    chronist.sender().shutdown().await;
+}
+
+#[tokio::test]
+async fn sender_survives_a_failed_transaction() {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"failed transaction")
+        .unwrap();
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        error_backoff: Duration::from_millis(100),
+        ..BatchingPolicy::default()
+    });
+    let mut events = chronist.subscribe_events();
+
+    ledger.set_post_failure(Some(PostFailure::Rejected));
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    loop {
+        if let ChronistEvent::TransactionFailed { .. } = events.recv().await.unwrap() {
+            break;
+        }
+    }
+    let health = chronist.sender().health();
+    assert_eq!(health.status, SenderStatus::Running);
+    assert!(health.last_error.is_some());
+    assert!(health.consecutive_failures > 0);
+
+    // the message stays pending and is sent once the node accepts transactions again
+    ledger.set_post_failure(None);
+    loop {
+        if events.recv().await.unwrap() == (ChronistEvent::ProofReady { message_id }) {
+            break;
+        }
+    }
+    let health = chronist.sender().health();
+    assert_eq!(health.status, SenderStatus::Running);
+    assert_eq!(health.consecutive_failures, 0);
+    chronist.sender().shutdown().await;
+}
+
+#[tokio::test]
+async fn resume_does_not_skip_the_backoff() {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"backoff")
+        .unwrap();
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        error_backoff: Duration::from_secs(5),
+        ..BatchingPolicy::default()
+    });
+    let mut events = chronist.subscribe_events();
+    ledger.set_post_failure(Some(PostFailure::Rejected));
+    chronist.sender().pause();
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    chronist.sender().resume();
+
+    loop {
+        if let ChronistEvent::TransactionFailed { .. } = events.recv().await.unwrap() {
+            break;
+        }
+    }
+    // the next try only happens after the backoff
+    let next_event = tokio::time::timeout(Duration::from_secs(2), events.recv()).await;
+    assert!(next_event.is_err());
+    assert_eq!(chronist.sender().health().consecutive_failures, 1);
+    chronist.sender().shutdown().await;
+}
+
+#[tokio::test]
+async fn dropping_the_chronist_stops_the_sender() {
+    let (_, chronist) = common::memory_chronist(common::config()).await;
+    let sender = chronist.sender();
+    drop(chronist);
+    assert_eq!(sender.status(), SenderStatus::Stopped);
+    // the sender thread ends
+    tokio::time::timeout(Duration::from_secs(10), sender.shutdown())
+        .await
+        .unwrap();
+    assert_eq!(sender.health().last_error, None);
+}
+
+#[test]
+fn error_backoff() {
+    let policy = BatchingPolicy {
+        error_backoff: Duration::from_secs(10),
+        max_error_backoff: Duration::from_secs(60),
+        ..BatchingPolicy::default()
+    };
+    assert_eq!(policy.backoff(1), Duration::from_secs(10));
+    assert_eq!(policy.backoff(2), Duration::from_secs(20));
+    assert_eq!(policy.backoff(3), Duration::from_secs(40));
+    assert_eq!(policy.backoff(4), Duration::from_secs(60));
+    assert_eq!(policy.backoff(100), Duration::from_secs(60));
+}
+
+#[tokio::test]
+async fn hashes_are_not_starved_by_message_ids() {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    let sender = chronist.sender();
+    sender.pause();
+
+    // more message ids than fit into a transaction, the hash is submitted between them
+    let hash = ContentHash::from_data(b"not starved");
+    let mut message_ids = Vec::new();
+    for i in 0..150u32 {
+        if i == 50 {
+            chronist.save_hash(hash).await.unwrap();
+        }
+        let message_id = ledger
+            .add_indexation_message("tangleproof", &i.to_be_bytes())
+            .unwrap();
+        chronist
+            .save_message(&message_id.to_string())
+            .await
+            .unwrap();
+        message_ids.push(message_id);
+    }
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        ..BatchingPolicy::default()
+    });
+    sender.resume();
+
+    // the hash is in the first transaction together with the message ids that were submitted around it
+    let hash_position = tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            match chronist.get_hash(&hash.to_hex(), None).await {
+                Ok(hash_wrapper) if hash_wrapper.inclusion_position.is_some() => {
+                    break hash_wrapper.inclusion_position
+                }
+                _ => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
+    })
+    .await
+    .unwrap();
+    let first_message = chronist
+        .get_message(&message_ids[0].to_string())
+        .await
+        .unwrap();
+    assert_eq!(first_message.inclusion_position, hash_position);
+    let last_message = chronist
+        .get_message(&message_ids[149].to_string())
+        .await
+        .unwrap();
+    assert_ne!(last_message.inclusion_position, hash_position);
+    sender.shutdown().await;
+}
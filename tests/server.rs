@@ -0,0 +1,44 @@
+use std::time::Duration;
+use tangleproof::server;
+use tokio::sync::oneshot;
+
+mod common;
+
+#[tokio::test]
+async fn open_event_stream_does_not_block_the_shutdown() {
+    let (_ledger, chronist) = common::memory_chronist(common::config()).await;
+    let port = std::net::TcpListener::bind(("127.0.0.1", 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(server::start_with_shutdown(chronist, port, async move {
+        let _ = stopped.await;
+    }));
+
+    // wait until the server accepts connections
+    let mut events = None;
+    for _ in 0..50 {
+        if let Ok(response) = reqwest::get(format!("http://127.0.0.1:{}/events", port)).await {
+            events = Some(response);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut events = events.expect("server didn't start");
+    assert!(events.status().is_success());
+
+    stop.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(10), server)
+        .await
+        .expect("shutdown waits for the event stream")
+        .unwrap()
+        .unwrap();
+    // the stream got closed by the server
+    let rest = tokio::time::timeout(Duration::from_secs(10), async {
+        while events.chunk().await.unwrap().is_some() {}
+    })
+    .await;
+    assert!(rest.is_ok());
+}
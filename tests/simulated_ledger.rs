@@ -0,0 +1,511 @@
+use iota_client::bee_message::MessageId;
+use std::{sync::Arc, time::Duration};
+use tangleproof::{
+    chronist::{Chronist, ChronistConfig, ContentHash, SaveMessageStatus},
+    error::Error,
+    events::ChronistEvent,
+    inclusion_proof::{HashInclusionProof, InclusionProof},
+    ledger::LedgerBackend,
+    pending_queue::Priority,
+    sender::BatchingPolicy,
+    simulated_ledger::{PostFailure, SimulatedLedger},
+    validation::{CheckStatus, ProofStepKind},
+};
+
+mod common;
+
+use common::SEED;
+
+#[tokio::test]
+async fn offline_proof() {
+    let db_path = "testdb_simulated_ledger";
+    let _ = std::fs::remove_dir_all(db_path);
+    let config = ChronistConfig {
+        rows: 3,
+        section_length: 2,
+        amount: 1_000_000,
+    };
+
+    let ledger = common::funded_ledger(config).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"offline")
+        .unwrap();
+
+    let chronist = Chronist::with_ledger(db_path, ledger.clone(), SEED, config)
+        .await
+        .unwrap();
+    let mut events = chronist.subscribe_events();
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    let hash = ContentHash::from_data(b"document");
+    assert_eq!(
+        chronist.save_hash(hash).await.unwrap(),
+        SaveMessageStatus::Accepted
+    );
+
+    let proof = loop {
+        match chronist.get_message_proof(&message_id.to_string()).await {
+            Ok(proof) => break proof,
+            Err(Error::InclusionPositionNotSet) => {
+                tokio::time::sleep(Duration::from_millis(500)).await
+            }
+            Err(e) => panic!("{}", e),
+        }
+    };
+    assert_eq!(proof.message.id().0, message_id);
+    assert_eq!(
+        events.recv().await.unwrap(),
+        ChronistEvent::MessageAccepted { message_id }
+    );
+    loop {
+        if events.recv().await.unwrap() == (ChronistEvent::ProofReady { message_id }) {
+            break;
+        }
+    }
+    loop {
+        if events.recv().await.unwrap() == (ChronistEvent::HashProofReady { hash }) {
+            break;
+        }
+    }
+    assert!(proof.is_valid(ledger.as_ref()).await.unwrap());
+
+    let verdict = proof.verdict(ledger.as_ref()).await;
+    assert!(verdict.valid);
+    assert!(verdict
+        .steps
+        .iter()
+        .any(|step| step.step == ProofStepKind::OutputLiveness));
+
+    let report = proof.verify_offline();
+    assert!(report.is_valid());
+    assert_eq!(report.latest_output_unspent.status, CheckStatus::Unverified);
+
+    let anchored_proof = chronist
+        .get_message_proof_with_milestone(&message_id.to_string())
+        .await
+        .unwrap();
+    assert!(anchored_proof.milestone_anchor.is_some());
+    assert!(anchored_proof.is_valid(ledger.as_ref()).await.unwrap());
+
+    let proof_bytes = anchored_proof.to_bytes(&config).unwrap();
+    let (decoded_proof, decoded_config) = InclusionProof::from_bytes(&proof_bytes).unwrap();
+    assert_eq!(decoded_config, config);
+    assert_eq!(
+        serde_json::to_string(&decoded_proof).unwrap(),
+        serde_json::to_string(&anchored_proof).unwrap()
+    );
+    let mut unknown_version = proof_bytes.clone();
+    unknown_version[0] += 1;
+    assert!(InclusionProof::from_bytes(&unknown_version).is_err());
+    // a section length in the header that would overflow the position math
+    let mut invalid_config = proof_bytes;
+    invalid_config[9..17].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(
+        InclusionProof::from_bytes(&invalid_config),
+        Err(Error::InvalidParameter(_))
+    ));
+
+    let proofs = chronist
+        .get_message_proofs(&[message_id.to_string(), "unknown".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(
+        proofs[0].1.as_ref().unwrap().transactions.len(),
+        proof.transactions.len()
+    );
+    assert!(proofs[1].1.is_err());
+
+    let statuses = chronist
+        .save_messages(&[message_id.to_string(), MessageId::new([1; 32]).to_string()])
+        .await;
+    assert_eq!(
+        *statuses[0].1.as_ref().unwrap(),
+        SaveMessageStatus::AlreadyIncluded {
+            inclusion_position: chronist
+                .get_message(&message_id.to_string())
+                .await
+                .unwrap()
+                .inclusion_position
+                .unwrap()
+        }
+    );
+    assert_eq!(
+        *statuses[1].1.as_ref().unwrap(),
+        SaveMessageStatus::NotFoundOnNode
+    );
+
+    let hash_proof = loop {
+        match chronist.get_hash_proof(&hash.to_hex(), None, true).await {
+            Ok(proof) => break proof,
+            Err(Error::InclusionPositionNotSet) => {
+                tokio::time::sleep(Duration::from_millis(500)).await
+            }
+            Err(e) => panic!("{}", e),
+        }
+    };
+    assert!(hash_proof.is_valid(ledger.as_ref()).await.unwrap());
+    let hash_verdict = hash_proof.verdict(ledger.as_ref()).await;
+    assert!(hash_verdict
+        .steps
+        .iter()
+        .any(|step| step.step == ProofStepKind::HashFound && step.status == CheckStatus::Passed));
+    let decoded_hash_proof: HashInclusionProof =
+        serde_json::from_str(&serde_json::to_string(&hash_proof).unwrap()).unwrap();
+    assert_eq!(decoded_hash_proof.hash, hash);
+
+    let refreshed_proof = chronist.refresh_proof(proof.clone()).await.unwrap();
+    assert_eq!(
+        refreshed_proof.transactions[0].id(),
+        proof.transactions[0].id()
+    );
+}
+
+#[tokio::test]
+async fn inputs_are_selected_from_the_seed() {
+    let ledger = SimulatedLedger::new();
+    let addresses = ledger.get_addresses(SEED, 3).await.unwrap();
+    ledger.fund(addresses[0], 5).unwrap();
+    ledger.fund(addresses[1], 10).unwrap();
+
+    // without inputs the outputs on the addresses are used in their order until they cover the amount
+    ledger
+        .send_transaction(SEED, Vec::new(), vec![(addresses[2], 12)], None)
+        .await
+        .unwrap();
+    let received = ledger.get_address_outputs(&addresses[2]).await.unwrap();
+    assert_eq!(received.len(), 1);
+    assert!(ledger
+        .get_address_outputs(&addresses[1])
+        .await
+        .unwrap()
+        .is_empty());
+    // the remainder goes back to the address of the first input
+    assert_eq!(
+        ledger
+            .get_address_outputs(&addresses[0])
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    assert!(matches!(
+        ledger
+            .send_transaction(SEED, Vec::new(), vec![(addresses[2], 100)], None)
+            .await,
+        Err(Error::NotEnoughFunds)
+    ));
+}
+
+// Proof of the first of two messages that are sent in consecutive transactions of the same row
+async fn proof_with_two_transactions() -> InclusionProof {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        ..BatchingPolicy::default()
+    });
+
+    let mut message_ids = Vec::new();
+    for data in &["first", "second"] {
+        let message_id = ledger
+            .add_indexation_message("tangleproof", data.as_bytes())
+            .unwrap();
+        chronist
+            .save_message(&message_id.to_string())
+            .await
+            .unwrap();
+        loop {
+            match chronist.get_message_proof(&message_id.to_string()).await {
+                Ok(_) => break,
+                Err(Error::InclusionPositionNotSet) => {
+                    tokio::time::sleep(Duration::from_millis(100)).await
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+        message_ids.push(message_id);
+    }
+    let proof = chronist
+        .get_message_proof(&message_ids[0].to_string())
+        .await
+        .unwrap();
+    chronist.sender().shutdown().await;
+    assert_eq!(proof.transactions.len(), 2);
+    assert!(proof.verify_offline().is_valid());
+    proof
+}
+
+// Change a hex string in the JSON of a proof without changing its length
+fn tamper_hex(value: &mut serde_json::Value) {
+    let hex = value.as_str().unwrap();
+    let flipped = if hex.starts_with('0') { "1" } else { "0" };
+    *value = serde_json::Value::String(format!("{}{}", flipped, &hex[1..]));
+}
+
+#[tokio::test]
+async fn offline_verification_rejects_tampered_signature() {
+    let proof = proof_with_two_transactions().await;
+    let mut proof_json = serde_json::to_value(&proof).unwrap();
+    tamper_hex(&mut proof_json["transactions"][0]["unlockBlocks"][0]["signature"]["signature"]);
+    let tampered_proof: InclusionProof = serde_json::from_value(proof_json).unwrap();
+
+    let report = tampered_proof.verify_offline();
+    assert!(!report.is_valid());
+    assert_eq!(report.transaction_signatures[0].status, CheckStatus::Failed);
+    assert_eq!(report.transaction_chain.status, CheckStatus::Passed);
+}
+
+#[tokio::test]
+async fn offline_verification_rejects_tampered_path() {
+    let proof = proof_with_two_transactions().await;
+    let mut proof_json = serde_json::to_value(&proof).unwrap();
+    // the second transaction doesn't spend an output of the first one anymore
+    tamper_hex(&mut proof_json["transactions"][1]["essence"]["inputs"][0]["transactionId"]);
+    let tampered_proof: InclusionProof = serde_json::from_value(proof_json).unwrap();
+
+    let report = tampered_proof.verify_offline();
+    assert!(!report.is_valid());
+    assert_eq!(report.transaction_chain.status, CheckStatus::Failed);
+}
+
+#[tokio::test]
+async fn concurrent_submissions_are_accepted_once() {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"concurrent")
+        .unwrap();
+    chronist.sender().pause();
+
+    let message_id = message_id.to_string();
+    let statuses =
+        futures::future::join_all((0..10).map(|_| chronist.save_message_with_status(&message_id)))
+            .await;
+    let accepted = statuses
+        .iter()
+        .filter(|status| *status.as_ref().unwrap() == SaveMessageStatus::Accepted)
+        .count();
+    assert_eq!(accepted, 1);
+    assert!(statuses.iter().all(|status| matches!(
+        status.as_ref().unwrap(),
+        SaveMessageStatus::Accepted | SaveMessageStatus::AlreadyPending
+    )));
+    chronist.sender().shutdown().await;
+}
+
+#[tokio::test]
+async fn merkle_tree_setting_survives_restart() {
+    let db_path = "testdb_merkle_tree_setting";
+    let _ = std::fs::remove_dir_all(db_path);
+    let config = common::config();
+    let ledger = common::funded_ledger(config).await;
+
+    let chronist = Chronist::with_ledger(db_path, ledger.clone(), SEED, config)
+        .await
+        .unwrap();
+    chronist.use_merkle_tree(true).await.unwrap();
+    chronist.sender().shutdown().await;
+    drop(chronist);
+
+    let chronist = Chronist::with_ledger(db_path, ledger.clone(), SEED, config)
+        .await
+        .unwrap();
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        ..BatchingPolicy::default()
+    });
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"restart")
+        .unwrap();
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    let proof = loop {
+        match chronist.get_message_proof(&message_id.to_string()).await {
+            Ok(proof) => break proof,
+            Err(Error::InclusionPositionNotSet) => {
+                tokio::time::sleep(Duration::from_millis(100)).await
+            }
+            Err(e) => panic!("{}", e),
+        }
+    };
+    assert!(proof.merkle_proof.is_some());
+    assert!(proof.verify_offline().is_valid());
+    chronist.sender().shutdown().await;
+}
+
+#[tokio::test]
+async fn pending_entries_survive_restart() {
+    let db_path = "testdb_pending_entries";
+    let _ = std::fs::remove_dir_all(db_path);
+    let config = common::config();
+    let ledger = common::funded_ledger(config).await;
+    let normal = ledger
+        .add_indexation_message("tangleproof", b"normal")
+        .unwrap();
+    let urgent = ledger
+        .add_indexation_message("tangleproof", b"urgent")
+        .unwrap();
+    let hash = ContentHash::from_data(b"pending document");
+
+    let chronist = Chronist::with_ledger(db_path, ledger.clone(), SEED, config)
+        .await
+        .unwrap();
+    chronist.sender().pause();
+    chronist.save_message(&normal.to_string()).await.unwrap();
+    chronist
+        .save_message_with_priority(&urgent.to_string(), Priority::High)
+        .await
+        .unwrap();
+    chronist.save_hash(hash).await.unwrap();
+    chronist.sender().shutdown().await;
+    drop(chronist);
+
+    // the queue keeps its order after the restart
+    let chronist = Chronist::with_ledger(db_path, ledger.clone(), SEED, config)
+        .await
+        .unwrap();
+    for (message_id, position) in &[(urgent, 0), (normal, 1)] {
+        let queue_position = chronist
+            .get_queue_position(&message_id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(queue_position.position, *position);
+        // the hash is pending too
+        assert_eq!(queue_position.pending, 3);
+    }
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        ..BatchingPolicy::default()
+    });
+    loop {
+        match chronist.get_hash_proof(&hash.to_hex(), None, false).await {
+            Ok(_) => break,
+            Err(Error::InclusionPositionNotSet) => {
+                tokio::time::sleep(Duration::from_millis(100)).await
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+    for message_id in &[urgent, normal] {
+        assert!(chronist
+            .get_message(&message_id.to_string())
+            .await
+            .unwrap()
+            .inclusion_position
+            .is_some());
+        assert!(chronist
+            .get_queue_position(&message_id.to_string())
+            .await
+            .is_err());
+    }
+    chronist.sender().shutdown().await;
+}
+
+// Let the first transaction for the message fail, like the node or the Chronist did while it got posted, and stop
+async fn fail_first_transaction(
+    db_path: &str,
+    ledger: Arc<SimulatedLedger>,
+    config: ChronistConfig,
+    message_id: MessageId,
+    failure: PostFailure,
+) {
+    let chronist = Chronist::with_ledger(db_path, ledger.clone(), SEED, config)
+        .await
+        .unwrap();
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        ..BatchingPolicy::default()
+    });
+    let mut events = chronist.subscribe_events();
+    ledger.set_post_failure(Some(failure));
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    loop {
+        if let ChronistEvent::TransactionFailed { .. } = events.recv().await.unwrap() {
+            break;
+        }
+    }
+    chronist.sender().shutdown().await;
+    ledger.set_post_failure(None);
+}
+
+#[tokio::test]
+async fn posted_transactions_are_recovered_after_restart() {
+    for (db_path, failure) in &[
+        ("testdb_recover_response_lost", PostFailure::ResponseLost),
+        ("testdb_recover_rejected", PostFailure::Rejected),
+    ] {
+        let _ = std::fs::remove_dir_all(db_path);
+        let config = common::config();
+        let ledger = common::funded_ledger(config).await;
+        let message_id = ledger
+            .add_indexation_message("tangleproof", b"recovery")
+            .unwrap();
+        fail_first_transaction(db_path, ledger.clone(), config, message_id, *failure).await;
+
+        // the transaction that got posted is found by its id, the rejected one is posted again
+        let chronist = Chronist::with_ledger(db_path, ledger.clone(), SEED, config)
+            .await
+            .unwrap();
+        assert!(chronist
+            .get_message(&message_id.to_string())
+            .await
+            .unwrap()
+            .inclusion_position
+            .is_some());
+        let proof = loop {
+            match chronist.get_message_proof(&message_id.to_string()).await {
+                Ok(proof) => break proof,
+                Err(Error::InclusionPositionNotSet) => {
+                    tokio::time::sleep(Duration::from_millis(100)).await
+                }
+                Err(e) => panic!("{}", e),
+            }
+        };
+        assert!(proof.is_valid(ledger.as_ref()).await.unwrap());
+        chronist.sender().shutdown().await;
+    }
+}
+
+#[tokio::test]
+async fn sender_recovers_a_transaction_with_a_lost_response() {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"lost response")
+        .unwrap();
+    chronist.set_batching_policy(BatchingPolicy {
+        batch_size: 1,
+        error_backoff: Duration::from_millis(100),
+        ..BatchingPolicy::default()
+    });
+    let mut events = chronist.subscribe_events();
+
+    // the transaction gets posted, but the sender gets an error
+    ledger.set_post_failure(Some(PostFailure::ResponseLost));
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    loop {
+        if let ChronistEvent::TransactionFailed { .. } = events.recv().await.unwrap() {
+            break;
+        }
+    }
+    ledger.set_post_failure(None);
+    loop {
+        if events.recv().await.unwrap() == (ChronistEvent::ProofReady { message_id }) {
+            break;
+        }
+    }
+    let proof = chronist
+        .get_message_proof(&message_id.to_string())
+        .await
+        .unwrap();
+    assert!(proof.is_valid(ledger.as_ref()).await.unwrap());
+    chronist.sender().shutdown().await;
+}
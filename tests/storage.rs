@@ -0,0 +1,182 @@
+use iota_client::bee_message::MessageId;
+use tangleproof::{
+    chronist::{ContentHash, HashAlgorithm, SaveMessageStatus},
+    storage::{MemoryStorage, Storage, StorageBatch, StorageColumn, StorageConfig, StorageKey},
+};
+
+mod common;
+
+// Same behaviour is expected from every backend
+async fn check_storage(mut storage: Box<dyn Storage>) {
+    let mut batch = StorageBatch::default();
+    for position in (1..=5u64).rev() {
+        batch
+            .set(StorageKey::Position(position), &position)
+            .unwrap();
+    }
+    batch.set(StorageKey::TransactionIndex, &6u64).unwrap();
+    storage.write_batch(batch).await.unwrap();
+
+    assert_eq!(
+        storage
+            .get_record(
+                StorageColumn::Metadata,
+                &StorageKey::TransactionIndex.to_bytes()
+            )
+            .await
+            .unwrap(),
+        Some(b"6".to_vec())
+    );
+    // keys of other columns aren't found
+    assert_eq!(
+        storage
+            .get_record(
+                StorageColumn::Positions,
+                &StorageKey::TransactionIndex.to_bytes()
+            )
+            .await
+            .unwrap(),
+        None
+    );
+
+    // positions are ordered
+    let records = storage
+        .records_after(StorageColumn::Positions, None, 2)
+        .await
+        .unwrap();
+    assert_eq!(
+        records,
+        vec![
+            (StorageKey::Position(1).to_bytes(), b"1".to_vec()),
+            (StorageKey::Position(2).to_bytes(), b"2".to_vec()),
+        ]
+    );
+    let records = storage
+        .records_after(StorageColumn::Positions, Some(&records[1].0), 10)
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].0, StorageKey::Position(3).to_bytes());
+
+    let mut batch = StorageBatch::default();
+    batch.delete(StorageKey::Position(3));
+    batch
+        .set(StorageKey::Callback(MessageId::new([1; 32])), &["url"])
+        .unwrap();
+    storage.write_batch(batch).await.unwrap();
+    assert_eq!(
+        storage
+            .prefix_records(StorageColumn::Positions, &[0; 7], None, 10)
+            .await
+            .unwrap()
+            .len(),
+        4
+    );
+    // prefix records in pages
+    let records = storage
+        .prefix_records(StorageColumn::Positions, &[0; 7], None, 3)
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 3);
+    let records = storage
+        .prefix_records(StorageColumn::Positions, &[0; 7], Some(&records[2].0), 3)
+        .await
+        .unwrap();
+    assert_eq!(
+        records,
+        vec![(StorageKey::Position(5).to_bytes(), b"5".to_vec())]
+    );
+    assert_eq!(
+        storage
+            .prefix_records(StorageColumn::Metadata, b"callback", None, 10)
+            .await
+            .unwrap(),
+        vec![(
+            StorageKey::Callback(MessageId::new([1; 32])).to_bytes(),
+            b"[\"url\"]".to_vec()
+        )]
+    );
+}
+
+#[tokio::test]
+async fn memory_storage() {
+    check_storage(Box::new(MemoryStorage::default())).await;
+    // a server must not lose its proofs at a restart
+    assert!(StorageConfig::from_name("memory", "testdb").is_err());
+}
+
+#[cfg(feature = "rocksdb")]
+#[tokio::test]
+async fn rocksdb_storage() {
+    let db_path = "testdb_rocksdb_storage";
+    let _ = std::fs::remove_dir_all(db_path);
+    check_storage(StorageConfig::Rocksdb(db_path.into()).open().unwrap()).await;
+    let _ = std::fs::remove_dir_all(db_path);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite_storage() {
+    let db_path = "testdb_sqlite_storage.sqlite";
+    let _ = std::fs::remove_file(db_path);
+    check_storage(StorageConfig::Sqlite(db_path.into()).open().unwrap()).await;
+    let _ = std::fs::remove_file(db_path);
+}
+
+#[tokio::test]
+async fn chronist_with_memory_storage() {
+    let (ledger, chronist) = common::memory_chronist(common::config()).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"memory")
+        .unwrap();
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    let message = chronist.get_message(&message_id.to_string()).await.unwrap();
+    assert_eq!(message.message.id().0, message_id);
+    assert_eq!(message.inclusion_position, None);
+}
+
+#[tokio::test]
+async fn same_digest_for_different_algorithms() {
+    let (_, chronist) = common::memory_chronist(common::config()).await;
+    chronist.sender().pause();
+
+    let sha256 = ContentHash {
+        algorithm: HashAlgorithm::Sha256,
+        digest: [7; 32],
+    };
+    let blake2b256 = ContentHash {
+        algorithm: HashAlgorithm::Blake2b256,
+        digest: [7; 32],
+    };
+    assert_ne!(
+        StorageKey::ContentHash(sha256).to_bytes(),
+        StorageKey::ContentHash(blake2b256).to_bytes()
+    );
+    assert_eq!(
+        chronist.save_hash(sha256).await.unwrap(),
+        SaveMessageStatus::Accepted
+    );
+    assert_eq!(
+        chronist.save_hash(blake2b256).await.unwrap(),
+        SaveMessageStatus::Accepted
+    );
+    assert_eq!(
+        chronist.save_hash(sha256).await.unwrap(),
+        SaveMessageStatus::AlreadyPending
+    );
+
+    // the algorithm is required, because the digest is stored for both of them
+    assert!(chronist.get_hash(&sha256.to_hex(), None).await.is_err());
+    assert_eq!(
+        chronist
+            .get_hash(&sha256.to_hex(), Some(HashAlgorithm::Blake2b256))
+            .await
+            .unwrap()
+            .hash,
+        blake2b256
+    );
+    chronist.sender().shutdown().await;
+}
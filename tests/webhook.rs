@@ -0,0 +1,131 @@
+use iota_client::bee_message::MessageId;
+use std::time::Duration;
+use tangleproof::{
+    chronist::ChronistConfig,
+    events::ChronistEvent,
+    inclusion_proof::InclusionProof,
+    webhook::{validate_callback_url, WebhookDelivery, MAX_DELIVERY_ATTEMPTS},
+};
+use warp::Filter;
+
+mod common;
+
+#[test]
+fn webhook_retries() {
+    let mut delivery = WebhookDelivery::new(
+        MessageId::new([0; 32]),
+        "http://localhost:8080/proof".to_string(),
+    );
+    let mut previous_attempt = delivery.next_attempt;
+    for attempt in 1..MAX_DELIVERY_ATTEMPTS {
+        delivery = delivery.retry().unwrap();
+        assert_eq!(delivery.attempts, attempt);
+        assert!(delivery.next_attempt > previous_attempt);
+        previous_attempt = delivery.next_attempt;
+    }
+    assert!(delivery.retry().is_none());
+}
+
+#[tokio::test]
+async fn callback_urls_need_a_public_host() {
+    for url in &[
+        "http://127.0.0.1:8080/proof",
+        "http://localhost/proof",
+        "http://api.localhost/proof",
+        "http://10.0.0.1/proof",
+        "http://192.168.1.1/proof",
+        "http://169.254.169.254/latest/meta-data",
+        "http://100.64.0.1/proof",
+        "http://0.0.0.0/proof",
+        "http://[::1]/proof",
+        "http://[fd00::1]/proof",
+        "http://[::ffff:127.0.0.1]/proof",
+        "file:///etc/passwd",
+        "ftp://93.184.216.34/proof",
+        "not a url",
+    ] {
+        assert!(
+            validate_callback_url(url, false).await.is_err(),
+            "{} was accepted",
+            url
+        );
+    }
+    assert!(validate_callback_url("https://93.184.216.34/proof", false)
+        .await
+        .is_ok());
+    // private hosts only if they're allowed, but still only http(s)
+    assert!(validate_callback_url("http://127.0.0.1:8080/proof", true)
+        .await
+        .is_ok());
+    assert!(validate_callback_url("file:///etc/passwd", true)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn proofs_are_posted_once_the_transaction_is_confirmed() {
+    // receiver of the proofs, with the path to distinguish the callbacks
+    let (proofs, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let routes = warp::post()
+        .and(warp::path::full())
+        .and(warp::body::json())
+        .map(move |path: warp::path::FullPath, proof: InclusionProof| {
+            proofs
+                .send((path.as_str().to_string(), proof.message.id().0))
+                .unwrap();
+            warp::reply()
+        });
+    let (address, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let config = ChronistConfig {
+        rows: 3,
+        section_length: 2,
+        amount: 1_000_000,
+    };
+    let ledger = common::funded_ledger(config).await;
+    let message_id = ledger
+        .add_indexation_message("tangleproof", b"webhook")
+        .unwrap();
+    let chronist = common::chronist_with_ledger(ledger.clone(), config).await;
+    chronist.allow_private_callback_hosts(true);
+    let mut events = chronist.subscribe_events();
+
+    // registered before and after the message got included
+    chronist
+        .register_callback(
+            &message_id.to_string(),
+            &format!("http://{}/before", address),
+        )
+        .await
+        .unwrap();
+    chronist
+        .save_message(&message_id.to_string())
+        .await
+        .unwrap();
+    loop {
+        if events.recv().await.unwrap() == (ChronistEvent::ProofReady { message_id }) {
+            break;
+        }
+    }
+    chronist
+        .register_callback(
+            &message_id.to_string(),
+            &format!("http://{}/after", address),
+        )
+        .await
+        .unwrap();
+
+    let mut paths = Vec::new();
+    for _ in 0..2 {
+        let (path, proof_message_id) =
+            tokio::time::timeout(Duration::from_secs(30), received.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(proof_message_id, message_id);
+        paths.push(path);
+    }
+    paths.sort();
+    assert_eq!(paths, vec!["/after".to_string(), "/before".to_string()]);
+}